@@ -11,11 +11,10 @@ use std::time::Duration;
 use disperser::disperser_client::DisperserClient;
 use disperser::{BlobStatus, BlobStatusRequest, DisperseBlobRequest, RetrieveBlobRequest};
 
-/// Publishes a blob to the Disperser.
+/// Publishes a blob to the Disperser at `endpoint`.
 /// Returns the request ID after verifying blob status
-pub async fn publish_blob(d: String) -> Result<String, Box<dyn std::error::Error>> {
-    let endpoint = "https://disperser-holesky.eigenda.xyz:443";
-    let mut client = DisperserClient::connect(endpoint).await.unwrap();
+pub async fn publish_blob(endpoint: &str, d: String) -> Result<String, Box<dyn std::error::Error>> {
+    let mut client = DisperserClient::connect(endpoint.to_string()).await?;
 
     let data = d.as_bytes().to_vec();
     println!("{:?}", data);
@@ -65,11 +64,13 @@ pub async fn publish_blob(d: String) -> Result<String, Box<dyn std::error::Error
     Ok(base64::encode(&request_id))
 }
 
-/// Retrieves a blob from the Disperser.
+/// Retrieves a blob from the Disperser at `endpoint`.
 /// Takes a request ID string and returns the blob data as a string.
-pub async fn retrieve_blob(request_id: String) -> Result<String, Box<dyn std::error::Error>> {
-    let endpoint = "https://disperser-holesky.eigenda.xyz:443";
-    let mut client = DisperserClient::connect(endpoint).await.unwrap();
+pub async fn retrieve_blob(
+    endpoint: &str,
+    request_id: String,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut client = DisperserClient::connect(endpoint.to_string()).await?;
 
     let request_id_bytes = base64::decode(request_id)?;
 
@@ -107,12 +108,14 @@ pub async fn retrieve_blob(request_id: String) -> Result<String, Box<dyn std::er
 mod tests {
     use super::*;
 
+    const ENDPOINT: &str = "https://disperser-holesky.eigenda.xyz:443";
+
     #[test]
     fn publish_verify_data() {
         let original = String::from("00") + r#"{ "message": "hello world" }"#;
         let request_id = tokio::runtime::Runtime::new().unwrap().block_on(async {
             println!("Publishing blob: {}", original);
-            publish_blob(original.to_string()).await.unwrap()
+            publish_blob(ENDPOINT, original.to_string()).await.unwrap()
         });
 
         println!("Request ID: {}", request_id);
@@ -120,7 +123,7 @@ mod tests {
         // Now use request_id to retrieve the blob
         let result = tokio::runtime::Runtime::new()
             .unwrap()
-            .block_on(async { retrieve_blob(request_id).await.unwrap() });
+            .block_on(async { retrieve_blob(ENDPOINT, request_id).await.unwrap() });
 
         assert_eq!(result, original);
     }
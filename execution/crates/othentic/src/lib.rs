@@ -11,7 +11,7 @@ use alloy_sol_types::SolValue;
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
-use std::{error::Error, sync::OnceLock};
+use std::error::Error;
 
 #[derive(Debug, Deserialize)]
 struct JsonRpcResponse {
@@ -27,43 +27,27 @@ struct JsonRpcError {
     message: String,
 }
 
-#[derive(Debug)]
-struct Config {
-    private_key: String,
-    eth_rpc_url: String,
-}
-
-impl Config {
-    fn new(private_key: String, eth_rpc_url: String) -> Self {
-        Config {
-            private_key,
-            eth_rpc_url,
-        }
-    }
-}
-
-// Global Config instance
-static CONFIG: OnceLock<Config> = OnceLock::new();
-
-// Set up global Config (can be called once at initialization)
-pub fn init_config(private_key: String, eth_rpc_url: String) {
-    let state = Config::new(private_key, eth_rpc_url);
-    let _ = CONFIG.set(state);
+/// Performer key and aggregator tasks are sent with
+#[derive(Debug, Clone)]
+pub struct OthenticConfig {
+    /// Key of the performer, as hex without the `0x` prefix
+    pub private_key: String,
+    /// RPC of the Othentic aggregator
+    pub rpc_url: String,
 }
 
 pub async fn send_task(
+    config: &OthenticConfig,
     proof_of_task: String,
     task_definition_id: i32,
 ) -> Result<(), Box<dyn Error>> {
-    // Access global Config
-    let config = CONFIG.get().expect("Config is not initialized");
     let data = "hello";
     let result = Bytes::from(data.as_bytes().to_vec());
 
     // let task_definition_id = 0;
 
-    let decoded_key = hex::decode(&config.private_key).unwrap();
-    let signing_key = SigningKey::from_bytes(GenericArray::from_slice(&decoded_key)).unwrap();
+    let decoded_key = hex::decode(&config.private_key)?;
+    let signing_key = SigningKey::from_bytes(GenericArray::from_slice(&decoded_key))?;
     let signer = PrivateKeySigner::from_signing_key(signing_key);
 
     let performer_address = signer.address();
@@ -99,7 +83,7 @@ pub async fn send_task(
     ];
 
     // Call the RPC method (sendTask)
-    make_rpc_request(&config.eth_rpc_url, params).await?;
+    make_rpc_request(&config.rpc_url, params).await?;
 
     Ok(())
 }
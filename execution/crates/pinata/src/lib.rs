@@ -1,10 +1,28 @@
-use dotenvy::dotenv;
 use reqwest::header::{HeaderMap, HeaderValue};
 use reqwest::multipart;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::error::Error;
 
+/// Credentials of the Pinata account content is pinned with
+#[derive(Debug, Clone)]
+pub struct PinataConfig {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+impl PinataConfig {
+    fn headers(&self) -> Result<HeaderMap, Box<dyn Error>> {
+        let mut headers = HeaderMap::new();
+        headers.insert("pinata_api_key", HeaderValue::from_str(&self.api_key)?);
+        headers.insert(
+            "pinata_secret_api_key",
+            HeaderValue::from_str(&self.api_secret)?,
+        );
+        Ok(headers)
+    }
+}
+
 #[derive(Debug)]
 pub struct FileUploadParams {
     pub file_url: String,
@@ -21,17 +39,13 @@ pub struct PinataResponse {
 }
 
 pub async fn upload_file_from_url(
+    config: &PinataConfig,
     params: FileUploadParams,
 ) -> Result<PinataResponse, Box<dyn Error>> {
     let file_url = params.file_url.as_str();
     let file_name = &params.file_name;
     let file_type = params.file_type.as_str();
 
-    dotenv().ok();
-
-    let api_key = std::env::var("PINATA_API_KEY")?;
-    let api_secret = std::env::var("PINATA_API_SECRET")?;
-
     // Fetch the file from the URL
     let response = reqwest::get(file_url).await?;
     let file_bytes = response.bytes().await?;
@@ -41,9 +55,7 @@ pub async fn upload_file_from_url(
 
     // Prepare the request to Pinata
     let client = reqwest::Client::new();
-    let mut headers = HeaderMap::new();
-    headers.insert("pinata_api_key", HeaderValue::from_str(&api_key)?);
-    headers.insert("pinata_secret_api_key", HeaderValue::from_str(&api_secret)?);
+    let headers = config.headers()?;
 
     // Create the multipart form
     let form = multipart::Form::new()
@@ -75,17 +87,13 @@ pub async fn upload_file_from_url(
     }
 }
 
-pub async fn upload_json(json_data: Value) -> Result<PinataResponse, Box<dyn Error>> {
-    dotenv().ok();
-
-    let api_key = std::env::var("PINATA_API_KEY")?;
-    let api_secret = std::env::var("PINATA_API_SECRET")?;
-
+pub async fn upload_json(
+    config: &PinataConfig,
+    json_data: Value,
+) -> Result<PinataResponse, Box<dyn Error>> {
     // Prepare the request to Pinata
     let client = reqwest::Client::new();
-    let mut headers = HeaderMap::new();
-    headers.insert("pinata_api_key", HeaderValue::from_str(&api_key)?);
-    headers.insert("pinata_secret_api_key", HeaderValue::from_str(&api_secret)?);
+    let mut headers = config.headers()?;
     headers.insert("Content-Type", HeaderValue::from_static("application/json"));
 
     // Send the request to Pinata
@@ -107,3 +115,27 @@ pub async fn upload_json(json_data: Value) -> Result<PinataResponse, Box<dyn Err
         Err(error_text.into())
     }
 }
+
+pub async fn unpin(config: &PinataConfig, ipfs_hash: &str) -> Result<(), Box<dyn Error>> {
+    let client = reqwest::Client::new();
+    let headers = config.headers()?;
+
+    // Remove the pin, gateways may keep serving the content for a while
+    let res = client
+        .delete(format!(
+            "https://api.pinata.cloud/pinning/unpin/{}",
+            ipfs_hash
+        ))
+        .headers(headers)
+        .send()
+        .await?;
+
+    if res.status().is_success() {
+        println!("Unpinned successfully: {}", ipfs_hash);
+        Ok(())
+    } else {
+        let error_text = res.text().await?;
+        eprintln!("Failed to unpin {}: {}", ipfs_hash, error_text);
+        Err(error_text.into())
+    }
+}
@@ -7,7 +7,6 @@ use alloy::{
     sol_types::SolEvent,
 };
 use eyre::Result;
-use std::str::FromStr;
 use ForgeRegistry::{IPMetadata, IPRegistered};
 
 pub struct IPData {
@@ -15,6 +14,17 @@ pub struct IPData {
     pub hash: FixedBytes<32>,
 }
 
+/// Chain access of the wallet registering IP
+#[derive(Debug, Clone)]
+pub struct RegistrarConfig {
+    /// Story RPC the transactions are sent through
+    pub rpc_url: String,
+    /// Key of the batcher wallet, as hex
+    pub private_key: String,
+    /// Address of the ForgeRegistry proxy contract
+    pub proxy_address: Address,
+}
+
 sol!(
     #[allow(missing_docs)]
     #[sol(rpc)]
@@ -23,6 +33,7 @@ sol!(
 );
 
 pub async fn register_ip(
+    config: &RegistrarConfig,
     address: Address,
     name: String,
     ip_metatdata_uri: String,
@@ -32,40 +43,22 @@ pub async fn register_ip(
     app_id: String,
     submitter: Address,
 ) -> Result<IPData> {
-    let private_key = std::env::var("PRIVATE_KEY").map_err(|e| {
-        println!("Failed to get PRIVATE_KEY: {}", e);
-        e
+    let rpc_url = config.rpc_url.parse().inspect_err(|e| {
+        print!("Failed to parse RPC URL: {}", e);
     })?;
 
-    let rpc_url = std::env::var("STORY_RPC_URL")
-        .map_err(|e| {
-            println!("Failed to get RPC URL: {}", e);
-            e
-        })
-        .unwrap()
-        .parse()
-        .inspect_err(|e| {
-            print!("Failed to parse RPC URL: {}", e);
-        })?;
-
-    let signer: PrivateKeySigner = private_key.parse().map_err(|e| {
+    let signer: PrivateKeySigner = config.private_key.parse().map_err(|e| {
         println!("Failed to parse private key: {}", e);
         e
     })?;
 
-    // Use PROXY_ADDRESS instead of CONTRACT_ADDRESS
-    let proxy_address = std::env::var("PROXY_ADDRESS").map_err(|e| {
-        println!("Failed to get PROXY_ADDRESS: {}", e);
-        e
-    })?;
-
     let wallet = EthereumWallet::from(signer.clone());
 
     let provider = ProviderBuilder::new().wallet(wallet).on_http(rpc_url);
 
     // Verify the proxy contract
     let code = provider
-        .get_code_at(Address::from_str(&proxy_address)?)
+        .get_code_at(config.proxy_address)
         .await
         .map_err(|e| {
             println!("Failed to verify proxy contract: {}", e);
@@ -77,7 +70,7 @@ pub async fn register_ip(
     }
 
     // Initialize contract with proxy address
-    let contract = ForgeRegistry::new(Address::from_str(&proxy_address)?, provider.clone());
+    let contract = ForgeRegistry::new(config.proxy_address, provider.clone());
 
     let is_batcher = contract.batcherWallet().call().await?;
     println!(
@@ -129,17 +122,10 @@ pub async fn register_ip(
     Ok(IPData { ipid, hash })
 }
 
-pub async fn get_transaction_data(hash: FixedBytes<32>) -> Result<IPRegistered> {
-    let rpc_url = std::env::var("STORY_RPC_URL")
-        .map_err(|e| {
-            println!("Failed to get RPC URL: {}", e);
-            e
-        })
-        .unwrap()
-        .parse()
-        .inspect_err(|e| {
-            print!("Failed to parse RPC URL: {}", e);
-        })?;
+pub async fn get_transaction_data(rpc_url: &str, hash: FixedBytes<32>) -> Result<IPRegistered> {
+    let rpc_url = rpc_url.parse().inspect_err(|e| {
+        print!("Failed to parse RPC URL: {}", e);
+    })?;
     let provider = ProviderBuilder::new().on_http(rpc_url);
 
     let receipt = provider
@@ -163,15 +149,15 @@ pub async fn get_transaction_data(hash: FixedBytes<32>) -> Result<IPRegistered>
 mod tests {
     use super::*;
     use alloy::providers::Provider;
+    use std::str::FromStr;
 
     #[tokio::test]
     async fn test_register_ip() {
-        // Set up test environment variables
-        std::env::set_var(
-            "PROXY_ADDRESS",
-            "0xF9FD6e4b853DE1182c31d39EC904175bcE945853",
-        );
-        std::env::set_var("STORY_RPC_URL", "https://aeneid.storyrpc.io");
+        let config = RegistrarConfig {
+            rpc_url: "https://aeneid.storyrpc.io".to_string(),
+            private_key: std::env::var("PRIVATE_KEY").unwrap(),
+            proxy_address: Address::from_str("0xF9FD6e4b853DE1182c31d39EC904175bcE945853").unwrap(),
+        };
 
         let address = Address::from_str("0x37ad3634C2fA851847d19256F42ec0eD5ad6e7b4").unwrap();
         println!("address: {:?}", address);
@@ -187,15 +173,12 @@ mod tests {
         let rpc_url = "https://aeneid.storyrpc.io".parse().unwrap();
         let provider = ProviderBuilder::new().on_http(rpc_url);
 
-        let proxy_address = std::env::var("PROXY_ADDRESS").unwrap();
-        let code = provider
-            .get_code_at(Address::from_str(&proxy_address).unwrap())
-            .await
-            .unwrap();
+        let code = provider.get_code_at(config.proxy_address).await.unwrap();
         println!("Proxy contract code exists: {}", !code.is_empty());
 
         // Execute register_ip function
         let result = register_ip(
+            &config,
             address,
             name,
             ip_metadata_uri,
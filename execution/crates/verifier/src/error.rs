@@ -0,0 +1,49 @@
+use serde::Serialize;
+
+/// Reasons a presentation can be rejected by the Verifier
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum VerifierError {
+    #[error("Failed to deserialize presentation: {0}")]
+    Deserialization(String),
+
+    #[error("Failed to serialize presentation: {0}")]
+    Serialization(String),
+
+    #[error("Untrusted notary: {0}")]
+    UntrustedNotary(String),
+
+    #[error("Signature or commitment verification failed: {0}")]
+    InvalidPresentation(String),
+
+    #[error("Presentation does not disclose the server name")]
+    MissingServerName,
+
+    #[error("Presentation does not disclose a transcript")]
+    MissingTranscript,
+
+    #[error("Proof is too old: {0}")]
+    StaleProof(String),
+
+    #[error("Proof is dated in the future: {0}")]
+    FutureProof(String),
+
+    #[error("Failed to load notary trust store: {0}")]
+    TrustStore(String),
+}
+
+/// Reasons a transcript can't be read as HTTP
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HttpError {
+    #[error("Transcript holds no HTTP message")]
+    Empty,
+
+    #[error("Malformed HTTP message: {0}")]
+    Malformed(String),
+
+    #[error("Unsupported content encoding '{0}'")]
+    UnsupportedEncoding(String),
+
+    #[error("Failed to decode {0} body: {1}")]
+    Decoding(String, String),
+}
@@ -0,0 +1,178 @@
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use bincode::Options;
+use tlsn_core::presentation::Presentation;
+
+use crate::error::VerifierError;
+
+/// Standard base64 that doesn't care whether padding was stripped.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// URL-safe base64 that doesn't care whether padding was stripped.
+const BASE64_URL_SAFE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Encodings a serialized presentation may come in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentationFormat {
+    /// The presentation as JSON, as written by `serde_json`
+    Json,
+    /// The presentation as bincode, as written by the TLSN prover
+    Binary,
+    /// Bincode wrapped in base64, so it can travel in JSON strings
+    Base64,
+}
+
+impl PresentationFormat {
+    /// Detects the encoding of a serialized presentation.
+    ///
+    /// JSON always starts with an object, base64 only uses its own alphabet and anything else is
+    /// treated as raw bincode.
+    pub fn detect(bytes: &[u8]) -> Self {
+        let trimmed = bytes.trim_ascii();
+
+        if trimmed.first() == Some(&b'{') {
+            PresentationFormat::Json
+        } else if !trimmed.is_empty() && trimmed.iter().all(|&b| is_base64_byte(b)) {
+            PresentationFormat::Base64
+        } else {
+            PresentationFormat::Binary
+        }
+    }
+}
+
+/// Deserializes a presentation, detecting its format.
+///
+/// # Arguments
+///
+/// * `bytes` - The JSON, bincode or base64 wrapped bincode presentation.
+pub fn decode_presentation(bytes: &[u8]) -> Result<Presentation, VerifierError> {
+    match PresentationFormat::detect(bytes) {
+        PresentationFormat::Json => serde_json::from_slice(bytes)
+            .map_err(|e| VerifierError::Deserialization(format!("invalid JSON: {}", e))),
+        PresentationFormat::Binary => decode_bincode(bytes),
+        PresentationFormat::Base64 => decode_bincode(&decode_base64(bytes)?),
+    }
+}
+
+/// Serializes a presentation to the compact bincode format.
+pub fn encode_presentation(presentation: &Presentation) -> Result<Vec<u8>, VerifierError> {
+    bincode::serialize(presentation)
+        .map_err(|e| VerifierError::Serialization(format!("failed to encode bincode: {}", e)))
+}
+
+/// Re-encodes a presentation in any supported format as base64 wrapped bincode.
+///
+/// # Arguments
+///
+/// * `bytes` - The JSON, bincode or base64 wrapped bincode presentation.
+pub fn to_compact_base64(bytes: &[u8]) -> Result<String, VerifierError> {
+    let presentation = decode_presentation(bytes)?;
+    Ok(BASE64.encode(encode_presentation(&presentation)?))
+}
+
+fn decode_bincode(bytes: &[u8]) -> Result<Presentation, VerifierError> {
+    // bincode ignores the limit when deserializing a slice, it only applies to readers
+    bincode_options(bytes.len())
+        .deserialize_from(bytes)
+        .map_err(|e| VerifierError::Deserialization(format!("invalid bincode: {}", e)))
+}
+
+// the encoding of `bincode::serialize`, limited so a length prefix can't claim more than the
+// input holds and make us allocate it
+fn bincode_options(limit: usize) -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit as u64)
+}
+
+fn decode_base64(bytes: &[u8]) -> Result<Vec<u8>, VerifierError> {
+    let stripped: Vec<u8> = bytes
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+
+    let engine = if stripped.iter().any(|&b| b == b'-' || b == b'_') {
+        &BASE64_URL_SAFE
+    } else {
+        &BASE64
+    };
+
+    engine
+        .decode(stripped)
+        .map_err(|e| VerifierError::Deserialization(format!("invalid base64: {}", e)))
+}
+
+fn is_base64_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric()
+        || b.is_ascii_whitespace()
+        || matches!(b, b'+' | b'/' | b'-' | b'_' | b'=')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            PresentationFormat::detect(b"  {\"attestation\": {}}"),
+            PresentationFormat::Json
+        );
+        assert_eq!(
+            PresentationFormat::detect(b"AAECAwQF\nBgcICQ=="),
+            PresentationFormat::Base64
+        );
+        assert_eq!(
+            PresentationFormat::detect(&[0, 1, 2, 3, 255]),
+            PresentationFormat::Binary
+        );
+        assert_eq!(PresentationFormat::detect(b""), PresentationFormat::Binary);
+    }
+
+    #[test]
+    fn test_decode_base64_variants() {
+        let bytes = vec![0xfb, 0xff, 0xbf, 0x00, 0x01];
+        assert_eq!(decode_base64(b"+/+/AAE=").unwrap(), bytes);
+        assert_eq!(decode_base64(b"+/+/AAE").unwrap(), bytes);
+        assert_eq!(decode_base64(b"-_-_\nAAE=").unwrap(), bytes);
+        assert!(decode_base64(b"not base64!").is_err());
+    }
+
+    #[test]
+    fn test_malformed_presentations_are_rejected() {
+        for bytes in [
+            &b"{\"not\": \"a presentation\"}"[..],
+            b"AAECAwQF",
+            &[0xff; 16],
+        ] {
+            assert!(matches!(
+                decode_presentation(bytes),
+                Err(VerifierError::Deserialization(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_oversized_length_prefix_is_refused() {
+        let mut bytes = u64::MAX.to_le_bytes().to_vec();
+        bytes.extend_from_slice(b"data");
+        let error = bincode_options(bytes.len())
+            .deserialize_from::<_, String>(&bytes[..])
+            .unwrap_err();
+        assert!(matches!(*error, bincode::ErrorKind::SizeLimit));
+
+        let error = decode_presentation(&bytes).unwrap_err();
+        assert!(matches!(error, VerifierError::Deserialization(_)));
+    }
+}
@@ -0,0 +1,768 @@
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::{borrow::Cow, io::Read, ops::Range};
+
+use crate::error::HttpError;
+
+/// Upper bound on a decompressed body, so a tiny proof can't expand into gigabytes.
+pub const MAX_DECODED_BODY: usize = 32 * 1024 * 1024;
+
+/// A header field and where its value sits in the transcript
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpHeader {
+    pub name: String,
+    /// The value with surrounding whitespace removed and folded lines joined by a space
+    pub value: String,
+    /// Byte range of the value in the transcript, folded lines included
+    pub span: Range<usize>,
+}
+
+/// Header fields of a message, in transcript order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpHeaders(Vec<HttpHeader>);
+
+impl HttpHeaders {
+    /// Returns the first header called `name`, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&HttpHeader> {
+        let name = name.trim();
+        self.0
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns every header called `name`, ignoring case.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a HttpHeader> {
+        let name = name.trim();
+        self.0
+            .iter()
+            .filter(move |header| header.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns an iterator over all headers.
+    pub fn iter(&self) -> impl Iterator<Item = &HttpHeader> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A message body with its transfer coding removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpBody {
+    /// The body after removing `Transfer-Encoding: chunked`, still content encoded
+    pub payload: Vec<u8>,
+    /// Byte range of the encoded body in the transcript
+    pub span: Range<usize>,
+    /// Whether the body was sent with chunked transfer coding
+    pub chunked: bool,
+    /// Content codings in the order they were applied, `identity` left out
+    pub content_encoding: Vec<String>,
+}
+
+impl HttpBody {
+    /// Returns the body with its content codings (gzip, deflate, br) undone.
+    pub fn decode(&self) -> Result<Cow<'_, [u8]>, HttpError> {
+        let mut decoded = Cow::Borrowed(self.payload.as_slice());
+        for coding in self.content_encoding.iter().rev() {
+            decoded = Cow::Owned(decode_content(coding, &decoded)?);
+        }
+        Ok(decoded)
+    }
+
+    /// Checks whether decoded offsets map one to one onto the transcript.
+    pub fn is_verbatim(&self) -> bool {
+        !self.chunked && self.content_encoding.is_empty()
+    }
+
+    /// Maps a range of the decoded body back onto the transcript.
+    ///
+    /// Encoded bodies can't be mapped byte by byte, so any range in them maps to the whole
+    /// encoded body.
+    pub fn transcript_span(&self, range: Range<usize>) -> Range<usize> {
+        if self.is_verbatim() {
+            self.span.start + range.start..self.span.start + range.end
+        } else {
+            self.span.clone()
+        }
+    }
+}
+
+/// An HTTP/1.x request parsed from the sent side of a transcript
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpRequest {
+    /// The request method, empty if the request line is missing or malformed
+    pub method: String,
+    /// The request target, usually an origin-form path with query
+    pub target: String,
+    pub version: String,
+    /// Byte range of the method in the transcript
+    pub method_span: Range<usize>,
+    /// Byte range of the target in the transcript
+    pub target_span: Range<usize>,
+    /// Byte range of the version in the transcript
+    pub version_span: Range<usize>,
+    pub headers: HttpHeaders,
+    pub body: HttpBody,
+    /// Byte range of the whole message in the transcript
+    pub span: Range<usize>,
+}
+
+/// An HTTP/1.x response parsed from the received side of a transcript
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub version: String,
+    /// The status code, `0` if the status line is missing or malformed
+    pub status: u16,
+    pub reason: String,
+    /// Byte range of the version in the transcript
+    pub version_span: Range<usize>,
+    /// Byte range of the status code in the transcript
+    pub status_span: Range<usize>,
+    /// Byte range of the reason phrase in the transcript, empty when there is none
+    pub reason_span: Range<usize>,
+    pub headers: HttpHeaders,
+    pub body: HttpBody,
+    /// Byte range of the whole message in the transcript
+    pub span: Range<usize>,
+}
+
+/// Parses the sent side of a transcript as an HTTP request.
+///
+/// # Arguments
+///
+/// * `transcript` - The sent bytes, redacted bytes included.
+pub fn parse_request(transcript: &[u8]) -> Result<HttpRequest, HttpError> {
+    parse_request_at(transcript, 0)
+}
+
+/// Parses the received side of a transcript as an HTTP response.
+///
+/// # Arguments
+///
+/// * `transcript` - The received bytes, redacted bytes included.
+pub fn parse_response(transcript: &[u8]) -> Result<HttpResponse, HttpError> {
+    parse_response_at(transcript, 0)
+}
+
+/// Parses every request sent over the connection, in order.
+///
+/// # Arguments
+///
+/// * `transcript` - The sent bytes, redacted bytes included.
+pub fn parse_requests(transcript: &[u8]) -> Result<Vec<HttpRequest>, HttpError> {
+    parse_all(transcript, parse_request_at, |request| request.span.end)
+}
+
+/// Parses every response received over the connection, in order.
+///
+/// A response without `Content-Length` or chunked coding runs until the end of the transcript,
+/// so only the messages before it can be told apart.
+///
+/// # Arguments
+///
+/// * `transcript` - The received bytes, redacted bytes included.
+pub fn parse_responses(transcript: &[u8]) -> Result<Vec<HttpResponse>, HttpError> {
+    parse_all(transcript, parse_response_at, |response| response.span.end)
+}
+
+/// A request and the response it got, matched by position on a keep-alive connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpExchange {
+    pub request: Option<HttpRequest>,
+    pub response: Option<HttpResponse>,
+}
+
+/// Pairs the messages of both sides of a transcript into exchanges.
+///
+/// # Arguments
+///
+/// * `sent` - The sent bytes, redacted bytes included.
+/// * `received` - The received bytes, redacted bytes included.
+pub fn parse_exchanges(sent: &[u8], received: &[u8]) -> Result<Vec<HttpExchange>, HttpError> {
+    let mut requests = parse_requests(sent)?.into_iter();
+    let mut responses = parse_responses(received)?.into_iter();
+
+    let mut exchanges = Vec::new();
+    loop {
+        match (requests.next(), responses.next()) {
+            (None, None) => return Ok(exchanges),
+            (request, response) => exchanges.push(HttpExchange { request, response }),
+        }
+    }
+}
+
+fn parse_all<T>(
+    bytes: &[u8],
+    parse: fn(&[u8], usize) -> Result<T, HttpError>,
+    end: fn(&T) -> usize,
+) -> Result<Vec<T>, HttpError> {
+    let mut messages = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        let message = match parse(bytes, pos) {
+            Ok(message) => message,
+            // Nothing but blank lines is left.
+            Err(HttpError::Empty) => return Ok(messages),
+            Err(e) => return Err(e),
+        };
+
+        let next = end(&message);
+        messages.push(message);
+        if next <= pos || next >= bytes.len() {
+            return Ok(messages);
+        }
+        pos = next;
+    }
+}
+
+fn parse_request_at(bytes: &[u8], pos: usize) -> Result<HttpRequest, HttpError> {
+    let head = parse_head(bytes, pos)?;
+
+    let mut request = HttpRequest::default();
+    if let Some(line) = &head.start_line {
+        let tokens = tokens(bytes, line.clone());
+        if let [method, target, version] = tokens.as_slice() {
+            if bytes[version.clone()].starts_with(b"HTTP/") {
+                request.method = String::from_utf8_lossy(&bytes[method.clone()]).to_string();
+                request.target = String::from_utf8_lossy(&bytes[target.clone()]).to_string();
+                request.version = String::from_utf8_lossy(&bytes[version.clone()]).to_string();
+                request.method_span = method.clone();
+                request.target_span = target.clone();
+                request.version_span = version.clone();
+            }
+        }
+    }
+
+    // Requests only carry a body when they announce one.
+    let framing = if head.inline_body {
+        Framing::UntilEnd
+    } else {
+        Framing::from_headers(&head.headers, Framing::Empty)?
+    };
+    request.body = parse_body(bytes, &head, framing)?;
+    request.span = head.start..request.body.span.end;
+    request.headers = head.headers;
+
+    Ok(request)
+}
+
+fn parse_response_at(bytes: &[u8], pos: usize) -> Result<HttpResponse, HttpError> {
+    let head = parse_head(bytes, pos)?;
+
+    let mut response = HttpResponse::default();
+    if let Some(line) = &head.start_line {
+        let tokens = tokens(bytes, line.clone());
+        if let [version, status, ..] = tokens.as_slice() {
+            let parsed = std::str::from_utf8(&bytes[status.clone()])
+                .ok()
+                .and_then(|status| status.parse().ok());
+            if let (true, Some(parsed)) = (bytes[version.clone()].starts_with(b"HTTP/"), parsed) {
+                // the reason phrase may contain spaces, it runs to the end of the line
+                let reason = trimmed(bytes, status.end..line.end);
+                response.version = String::from_utf8_lossy(&bytes[version.clone()]).to_string();
+                response.status = parsed;
+                response.reason = String::from_utf8_lossy(&bytes[reason.clone()]).to_string();
+                response.version_span = version.clone();
+                response.status_span = status.clone();
+                response.reason_span = reason;
+            }
+        }
+    }
+
+    // Informational, 204 and 304 responses never have a body.
+    let framing = if head.inline_body {
+        Framing::UntilEnd
+    } else if (100..200).contains(&response.status)
+        || response.status == 204
+        || response.status == 304
+    {
+        Framing::Empty
+    } else {
+        Framing::from_headers(&head.headers, Framing::UntilEnd)?
+    };
+    response.body = parse_body(bytes, &head, framing)?;
+    response.span = head.start..response.body.span.end;
+    response.headers = head.headers;
+
+    Ok(response)
+}
+
+/// The start line, headers and where the body begins
+struct Head {
+    start: usize,
+    start_line: Option<Range<usize>>,
+    headers: HttpHeaders,
+    body_start: usize,
+    /// The body was found without a blank line separator, it runs until the end
+    inline_body: bool,
+}
+
+/// A line of the transcript, `end` excludes the line terminator
+struct Line {
+    start: usize,
+    end: usize,
+    next: usize,
+}
+
+fn next_line(bytes: &[u8], pos: usize) -> Option<Line> {
+    if pos >= bytes.len() {
+        return None;
+    }
+
+    let rest = &bytes[pos..];
+    match rest.iter().position(|&b| b == b'\n') {
+        Some(newline) => {
+            let end = if newline > 0 && rest[newline - 1] == b'\r' {
+                pos + newline - 1
+            } else {
+                pos + newline
+            };
+            Some(Line {
+                start: pos,
+                end,
+                next: pos + newline + 1,
+            })
+        }
+        None => Some(Line {
+            start: pos,
+            end: bytes.len(),
+            next: bytes.len(),
+        }),
+    }
+}
+
+fn trimmed(bytes: &[u8], range: Range<usize>) -> Range<usize> {
+    let slice = &bytes[range.clone()];
+    let leading = slice.iter().take_while(|b| b.is_ascii_whitespace()).count();
+    let trailing = slice[leading..]
+        .iter()
+        .rev()
+        .take_while(|b| b.is_ascii_whitespace())
+        .count();
+    range.start + leading..range.end - trailing
+}
+
+// ranges of the whitespace separated tokens in `range`
+fn tokens(bytes: &[u8], range: Range<usize>) -> Vec<Range<usize>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for i in range.clone() {
+        match (bytes[i].is_ascii_whitespace(), start) {
+            (true, Some(token_start)) => {
+                tokens.push(token_start..i);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(token_start) = start {
+        tokens.push(token_start..range.end);
+    }
+    tokens
+}
+
+fn is_blank(bytes: &[u8], line: &Line) -> bool {
+    trimmed(bytes, line.start..line.end).is_empty()
+}
+
+// Header names can't start with these, so such a line must be a body sent without separator.
+fn starts_body(bytes: &[u8], line: &Line) -> bool {
+    let content = trimmed(bytes, line.start..line.end);
+    matches!(bytes.get(content.start), Some(b'{') | Some(b'['))
+}
+
+fn parse_head(bytes: &[u8], pos: usize) -> Result<Head, HttpError> {
+    // Skip blank lines left between messages.
+    let mut pos = pos;
+    let first = loop {
+        match next_line(bytes, pos) {
+            None => return Err(HttpError::Empty),
+            Some(line) if is_blank(bytes, &line) => pos = line.next,
+            Some(line) => break line,
+        }
+    };
+
+    if starts_body(bytes, &first) {
+        return Ok(Head {
+            start: first.start,
+            start_line: None,
+            headers: HttpHeaders::default(),
+            body_start: first.start,
+            inline_body: true,
+        });
+    }
+
+    let mut headers: Vec<HttpHeader> = Vec::new();
+    let mut base_indent = None;
+    let mut pos = first.next;
+
+    while let Some(line) = next_line(bytes, pos) {
+        if is_blank(bytes, &line) {
+            return Ok(Head {
+                start: first.start,
+                start_line: Some(first.start..first.end),
+                headers: HttpHeaders(headers),
+                body_start: line.next,
+                inline_body: false,
+            });
+        }
+
+        if starts_body(bytes, &line) {
+            return Ok(Head {
+                start: first.start,
+                start_line: Some(first.start..first.end),
+                headers: HttpHeaders(headers),
+                body_start: line.start,
+                inline_body: true,
+            });
+        }
+
+        let indent = bytes[line.start..line.end]
+            .iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+        let base = *base_indent.get_or_insert(indent);
+        let content = trimmed(bytes, line.start..line.end);
+
+        if indent > base && !headers.is_empty() {
+            // An obsolete line fold continues the previous header's value.
+            let last = headers.last_mut().unwrap();
+            last.value.push(' ');
+            last.value
+                .push_str(&String::from_utf8_lossy(&bytes[content.clone()]));
+            last.span.end = content.end;
+        } else if let Some(colon) = bytes[content.clone()].iter().position(|&b| b == b':') {
+            let name = trimmed(bytes, content.start..content.start + colon);
+            let value = trimmed(bytes, content.start + colon + 1..content.end);
+            headers.push(HttpHeader {
+                name: String::from_utf8_lossy(&bytes[name]).to_string(),
+                value: String::from_utf8_lossy(&bytes[value.clone()]).to_string(),
+                span: value,
+            });
+        }
+        // Lines without a colon aren't headers and are skipped.
+
+        pos = line.next;
+    }
+
+    Ok(Head {
+        start: first.start,
+        start_line: Some(first.start..first.end),
+        headers: HttpHeaders(headers),
+        body_start: bytes.len(),
+        inline_body: false,
+    })
+}
+
+/// How the end of a body is found
+enum Framing {
+    Empty,
+    Length(usize),
+    Chunked,
+    UntilEnd,
+}
+
+impl Framing {
+    fn from_headers(headers: &HttpHeaders, default: Framing) -> Result<Self, HttpError> {
+        let chunked = headers.get_all("transfer-encoding").any(|header| {
+            header
+                .value
+                .split(',')
+                .any(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+        });
+        if chunked {
+            return Ok(Framing::Chunked);
+        }
+
+        match headers.get("content-length") {
+            Some(header) => header
+                .value
+                .trim()
+                .parse()
+                .map(Framing::Length)
+                .map_err(|_| {
+                    HttpError::Malformed(format!("invalid content-length '{}'", header.value))
+                }),
+            None => Ok(default),
+        }
+    }
+}
+
+fn parse_body(bytes: &[u8], head: &Head, framing: Framing) -> Result<HttpBody, HttpError> {
+    let start = head.body_start.min(bytes.len());
+
+    let (payload, end, chunked) = match framing {
+        Framing::Empty => (Vec::new(), start, false),
+        // A proof may stop short of the announced length, keep what was disclosed.
+        Framing::Length(length) => {
+            let end = start.saturating_add(length).min(bytes.len());
+            (bytes[start..end].to_vec(), end, false)
+        }
+        Framing::Chunked => {
+            let (payload, end) = decode_chunked(bytes, start)?;
+            (payload, end, true)
+        }
+        Framing::UntilEnd => (bytes[start..].to_vec(), bytes.len(), false),
+    };
+
+    let content_encoding = head
+        .headers
+        .get_all("content-encoding")
+        .flat_map(|header| header.value.split(','))
+        .map(|coding| coding.trim().to_ascii_lowercase())
+        .filter(|coding| !coding.is_empty() && coding != "identity")
+        .collect();
+
+    Ok(HttpBody {
+        payload,
+        span: start..end,
+        chunked,
+        content_encoding,
+    })
+}
+
+fn decode_chunked(bytes: &[u8], start: usize) -> Result<(Vec<u8>, usize), HttpError> {
+    let mut payload = Vec::new();
+    let mut pos = start;
+
+    loop {
+        let line = next_line(bytes, pos)
+            .ok_or_else(|| HttpError::Malformed("chunked body ends without last chunk".into()))?;
+        let size_line = String::from_utf8_lossy(&bytes[line.start..line.end]);
+        let size_str = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_str, 16)
+            .map_err(|_| HttpError::Malformed(format!("invalid chunk size '{}'", size_str)))?;
+        pos = line.next;
+
+        if size == 0 {
+            // Skip the trailer section up to and including its blank line.
+            while let Some(line) = next_line(bytes, pos) {
+                pos = line.next;
+                if is_blank(bytes, &line) {
+                    break;
+                }
+            }
+            return Ok((payload, pos));
+        }
+
+        let end = pos
+            .checked_add(size)
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| HttpError::Malformed("chunk runs past the transcript".into()))?;
+        payload.extend_from_slice(&bytes[pos..end]);
+        pos = end;
+
+        if bytes[pos..].starts_with(b"\r\n") {
+            pos += 2;
+        } else if bytes[pos..].starts_with(b"\n") {
+            pos += 1;
+        } else if pos < bytes.len() {
+            return Err(HttpError::Malformed("chunk is not followed by CRLF".into()));
+        }
+    }
+}
+
+fn decode_content(coding: &str, bytes: &[u8]) -> Result<Vec<u8>, HttpError> {
+    match coding {
+        "gzip" | "x-gzip" => read_limited(GzDecoder::new(bytes), coding),
+        // Servers disagree on whether deflate is zlib wrapped, accept both.
+        "deflate" => read_limited(ZlibDecoder::new(bytes), coding)
+            .or_else(|_| read_limited(DeflateDecoder::new(bytes), coding)),
+        "br" => read_limited(brotli::Decompressor::new(bytes, 4096), coding),
+        _ => Err(HttpError::UnsupportedEncoding(coding.to_string())),
+    }
+}
+
+fn read_limited(reader: impl Read, coding: &str) -> Result<Vec<u8>, HttpError> {
+    let mut decoded = Vec::new();
+    reader
+        .take(MAX_DECODED_BODY as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|e| HttpError::Decoding(coding.to_string(), e.to_string()))?;
+
+    if decoded.len() > MAX_DECODED_BODY {
+        return Err(HttpError::Decoding(
+            coding.to_string(),
+            format!("body exceeds {} bytes", MAX_DECODED_BODY),
+        ));
+    }
+
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_request() {
+        let sent = b"GET /2/users/by/username/forge?user.fields=id HTTP/1.1\r\nHost: api.x.com\r\nAuthorization: Bearer ~~~~~~\r\n\r\n";
+        let request = parse_request(sent).unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.target, "/2/users/by/username/forge?user.fields=id");
+        assert_eq!(request.version, "HTTP/1.1");
+        assert_eq!(&sent[request.method_span.clone()], b"GET");
+        assert_eq!(
+            &sent[request.target_span.clone()],
+            request.target.as_bytes()
+        );
+        assert_eq!(request.headers.get("host").unwrap().value, "api.x.com");
+
+        let auth = request.headers.get("AUTHORIZATION").unwrap();
+        assert_eq!(&sent[auth.span.clone()], b"Bearer ~~~~~~");
+        assert!(request.body.payload.is_empty());
+        assert_eq!(request.span, 0..sent.len());
+    }
+
+    #[test]
+    fn test_parse_response_with_length() {
+        let received =
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 13\r\n\r\n{\"id\":\"1234\"}";
+        let response = parse_response(received).unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.reason, "OK");
+        assert_eq!(&received[response.version_span.clone()], b"HTTP/1.1");
+        assert_eq!(&received[response.status_span.clone()], b"200");
+        assert_eq!(&received[response.reason_span.clone()], b"OK");
+        assert_eq!(
+            response.body.decode().unwrap().as_ref(),
+            b"{\"id\":\"1234\"}"
+        );
+        assert_eq!(&received[response.body.span.clone()], b"{\"id\":\"1234\"}");
+        let id = response.body.transcript_span(7..11);
+        assert_eq!(&received[id], b"1234");
+    }
+
+    #[test]
+    fn test_folded_headers() {
+        let received = b"HTTP/1.1 200 OK\nX-Long: first,\n   second,\n\tthird\nX-Next: value\n\n";
+        let response = parse_response(received).unwrap();
+
+        let long = response.headers.get("x-long").unwrap();
+        assert_eq!(long.value, "first, second, third");
+        assert_eq!(&received[long.span.clone()], b"first,\n   second,\n\tthird");
+        assert_eq!(response.headers.get("x-next").unwrap().value, "value");
+    }
+
+    #[test]
+    fn test_chunked_gzip_body() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(br#"{"name":"forge"}"#).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+
+        let mut received =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Encoding: gzip\r\n\r\n"
+                .to_vec();
+        for chunk in [first, second] {
+            received.extend_from_slice(format!("{:x};ext=1\r\n", chunk.len()).as_bytes());
+            received.extend_from_slice(chunk);
+            received.extend_from_slice(b"\r\n");
+        }
+        received.extend_from_slice(b"0\r\nX-Trailer: yes\r\n\r\n");
+
+        let response = parse_response(&received).unwrap();
+        assert!(response.body.chunked);
+        assert!(!response.body.is_verbatim());
+        assert_eq!(response.body.payload, compressed);
+        assert_eq!(
+            response.body.decode().unwrap().as_ref(),
+            br#"{"name":"forge"}"#
+        );
+        assert_eq!(response.body.transcript_span(0..4), response.body.span);
+        assert_eq!(response.span, 0..received.len());
+    }
+
+    #[test]
+    fn test_brotli_and_deflate_bodies() {
+        let mut brotli = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut brotli, 4096, 5, 22);
+            writer.write_all(b"hello brotli").unwrap();
+        }
+        let mut received = b"HTTP/1.1 200 OK\r\nContent-Encoding: br\r\n\r\n".to_vec();
+        received.extend_from_slice(&brotli);
+        let response = parse_response(&received).unwrap();
+        assert_eq!(response.body.decode().unwrap().as_ref(), b"hello brotli");
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(b"hello deflate").unwrap();
+        let mut received = b"HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\n\r\n".to_vec();
+        received.extend_from_slice(&zlib.finish().unwrap());
+        let response = parse_response(&received).unwrap();
+        assert_eq!(response.body.decode().unwrap().as_ref(), b"hello deflate");
+    }
+
+    #[test]
+    fn test_malformed_bodies() {
+        let unsupported =
+            parse_response(b"HTTP/1.1 200 OK\r\nContent-Encoding: zstd\r\n\r\nabc").unwrap();
+        assert!(matches!(
+            unsupported.body.decode(),
+            Err(HttpError::UnsupportedEncoding(_))
+        ));
+
+        let redacted_chunk =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n~~\r\nabc\r\n0\r\n\r\n";
+        assert!(matches!(
+            parse_response(redacted_chunk),
+            Err(HttpError::Malformed(_))
+        ));
+
+        assert!(matches!(
+            parse_response(b"\r\n  \r\n"),
+            Err(HttpError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_keep_alive_exchanges() {
+        let sent = b"GET /profile HTTP/1.1\r\nHost: api.x.com\r\n\r\nPOST /posts HTTP/1.1\r\nHost: api.x.com\r\nContent-Length: 2\r\n\r\n{}";
+        let received = b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\n{\"id\":1}\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n8\r\n{\"id\":2}\r\n0\r\n\r\nHTTP/1.1 204 No Content\r\n\r\n";
+
+        let exchanges = parse_exchanges(sent, received).unwrap();
+        assert_eq!(exchanges.len(), 3);
+
+        let targets: Vec<_> = exchanges
+            .iter()
+            .map(|exchange| exchange.request.as_ref().map(|r| r.target.as_str()))
+            .collect();
+        assert_eq!(targets, vec![Some("/profile"), Some("/posts"), None]);
+
+        let responses: Vec<_> = exchanges
+            .iter()
+            .map(|exchange| exchange.response.as_ref().unwrap())
+            .collect();
+        assert_eq!(responses[0].body.payload, b"{\"id\":1}");
+        assert_eq!(responses[1].body.payload, b"{\"id\":2}");
+        assert_eq!(responses[2].status, 204);
+        assert_eq!(responses[2].span.end, received.len());
+
+        assert!(parse_requests(b"\r\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lenient_bodies() {
+        // JSON straight after the headers, without a blank line
+        let received = b"HTTP/1.1 200 OK\ncontent-type: application/json\n{\"a\":1}";
+        let response = parse_response(received).unwrap();
+        assert_eq!(response.headers.len(), 1);
+        assert_eq!(response.body.payload, b"{\"a\":1}");
+
+        // JSON without any head at all
+        let response = parse_response(b"\n  [1, 2]").unwrap();
+        assert_eq!(response.status, 0);
+        assert_eq!(response.body.payload, b"  [1, 2]");
+    }
+}
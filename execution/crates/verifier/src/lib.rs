@@ -1,6 +1,12 @@
-use elliptic_curve::pkcs8::DecodePublicKey;
+mod error;
+mod format;
+mod http;
+mod policy;
+mod ranges;
+mod trust;
+
 use serde::Serialize;
-use std::{str, time::Duration};
+use std::time::Duration;
 use tlsn_core::{
     connection::ServerName,
     presentation::{Presentation, PresentationOutput},
@@ -8,12 +14,66 @@ use tlsn_core::{
     CryptoProvider,
 };
 
+pub use error::{HttpError, VerifierError};
+pub use format::{decode_presentation, encode_presentation, to_compact_base64, PresentationFormat};
+pub use http::{
+    parse_exchanges, parse_request, parse_requests, parse_response, parse_responses, HttpBody,
+    HttpExchange, HttpHeader, HttpHeaders, HttpRequest, HttpResponse, MAX_DECODED_BODY,
+};
+pub use policy::{
+    VerificationPolicy, DEFAULT_MAX_AGE, DEFAULT_MAX_FUTURE_SKEW, DEFAULT_MAX_TASK_LAG, MAX_WINDOW,
+};
+pub use ranges::AuthenticatedRanges;
+pub use trust::{NotaryTrustStore, TrustedNotary, BUNDLED_NOTARY_LABEL, INLINE_NOTARY_LABEL};
+
 /// Verifies a TLS proof from a JSON string and returns the verified data
+///
+/// # Arguments
+///
+/// * `proof_json` - The JSON encoded presentation.
+/// * `trust_store` - The notaries whose attestations are accepted.
+/// * `policy` - The rules the connection time must satisfy.
 pub fn verify_proof_from_json(
     proof_json: &str,
-) -> Result<VerificationResult, Box<dyn std::error::Error>> {
+    trust_store: &NotaryTrustStore,
+    policy: &VerificationPolicy,
+) -> Result<VerificationResult, VerifierError> {
     // Deserialize the proof directly from the provided JSON string
-    let presentation: Presentation = serde_json::from_str(proof_json)?;
+    let presentation: Presentation = serde_json::from_str(proof_json)
+        .map_err(|e| VerifierError::Deserialization(e.to_string()))?;
+
+    verify_presentation(presentation, trust_store, policy)
+}
+
+/// Verifies a TLS proof in JSON, bincode or base64 wrapped bincode and returns the verified data
+///
+/// # Arguments
+///
+/// * `proof` - The encoded presentation, its format is detected.
+/// * `trust_store` - The notaries whose attestations are accepted.
+/// * `policy` - The rules the connection time must satisfy.
+pub fn verify_proof_from_bytes(
+    proof: &[u8],
+    trust_store: &NotaryTrustStore,
+    policy: &VerificationPolicy,
+) -> Result<VerificationResult, VerifierError> {
+    let presentation = decode_presentation(proof)?;
+
+    verify_presentation(presentation, trust_store, policy)
+}
+
+/// Verifies a deserialized presentation and returns the verified data
+///
+/// # Arguments
+///
+/// * `presentation` - The presentation to verify.
+/// * `trust_store` - The notaries whose attestations are accepted.
+/// * `policy` - The rules the connection time must satisfy.
+pub fn verify_presentation(
+    presentation: Presentation,
+    trust_store: &NotaryTrustStore,
+    policy: &VerificationPolicy,
+) -> Result<VerificationResult, VerifierError> {
     let provider = CryptoProvider::default();
 
     let VerifyingKey {
@@ -21,8 +81,12 @@ pub fn verify_proof_from_json(
         data: key_data,
     } = presentation.verifying_key();
 
-    if hex::encode(key_data) != notary_pubkey().to_string() {
-        return Err("Notary public key doesn't match".into());
+    let candidates = trust_store.find_by_key(key_data);
+    if candidates.is_empty() {
+        return Err(VerifierError::UntrustedNotary(format!(
+            "public key {} is not in the trust store",
+            hex::encode(key_data)
+        )));
     }
 
     // Verify the presentation
@@ -31,38 +95,99 @@ pub fn verify_proof_from_json(
         connection_info,
         transcript,
         ..
-    } = presentation.verify(&provider).unwrap();
+    } = presentation
+        .verify(&provider)
+        .map_err(|e| VerifierError::InvalidPresentation(e.to_string()))?;
 
     // The time at which the connection was started
     let time = chrono::DateTime::UNIX_EPOCH + Duration::from_secs(connection_info.time);
+    policy.check(time)?;
+
+    // Rotated keys are only trusted for connections made within their validity window
+    let notary = candidates
+        .into_iter()
+        .find(|notary| notary.is_valid_at(time))
+        .ok_or_else(|| {
+            VerifierError::UntrustedNotary(format!("key is not valid at {}", time.to_rfc3339()))
+        })?;
 
-    let server_name = server_name.unwrap();
-    let mut partial_transcript = transcript.unwrap();
+    let server_name = server_name.ok_or(VerifierError::MissingServerName)?;
+    let mut partial_transcript = transcript.ok_or(VerifierError::MissingTranscript)?;
     // Set the unauthenticated bytes so they are distinguishable.
     partial_transcript.set_unauthed(b'~');
 
+    let sent_authed = AuthenticatedRanges::new(
+        partial_transcript.sent_authed().iter_ranges(),
+        partial_transcript.sent_unsafe().len(),
+    );
+    let received_authed = AuthenticatedRanges::new(
+        partial_transcript.received_authed().iter_ranges(),
+        partial_transcript.received_unsafe().len(),
+    );
+
     Ok(VerificationResult {
         server_name,
         time,
+        notary: notary.label.clone(),
         sent_data: String::from_utf8_lossy(partial_transcript.sent_unsafe()).to_string(),
         received_data: String::from_utf8_lossy(partial_transcript.received_unsafe()).to_string(),
+        sent_authed,
+        received_authed,
+        sent_raw: partial_transcript.sent_unsafe().to_vec(),
+        received_raw: partial_transcript.received_unsafe().to_vec(),
     })
 }
 
 /// Struct to hold the verification results
+///
+/// The authenticated ranges index the raw transcript bytes, kept in `sent_raw` and
+/// `received_raw`. They line up with the strings as long as the transcript is valid UTF-8.
 #[derive(Debug, Serialize)]
 pub struct VerificationResult {
     pub server_name: ServerName,
     #[serde(serialize_with = "serialize_datetime")]
     pub time: chrono::DateTime<chrono::Utc>,
+    /// Label of the trusted notary that signed the presentation
+    pub notary: String,
     pub sent_data: String,
     pub received_data: String,
+    /// Byte ranges of `sent_data` that were authenticated, the rest is redacted
+    pub sent_authed: AuthenticatedRanges,
+    /// Byte ranges of `received_data` that were authenticated, the rest is redacted
+    pub received_authed: AuthenticatedRanges,
+    /// The sent bytes, redacted bytes replaced with `~`
+    #[serde(skip)]
+    pub sent_raw: Vec<u8>,
+    /// The received bytes, redacted bytes replaced with `~`
+    #[serde(skip)]
+    pub received_raw: Vec<u8>,
 }
 
-/// Returns a Notary pubkey trusted by this Verifier
-fn notary_pubkey() -> p256::PublicKey {
-    let pem_file = str::from_utf8(include_bytes!("../../../fixture/notary/notary.pub")).unwrap();
-    p256::PublicKey::from_public_key_pem(pem_file).unwrap()
+impl VerificationResult {
+    /// Parses the sent data as an HTTP request.
+    pub fn request(&self) -> Result<HttpRequest, HttpError> {
+        parse_request(&self.sent_raw)
+    }
+
+    /// Parses the received data as an HTTP response.
+    pub fn response(&self) -> Result<HttpResponse, HttpError> {
+        parse_response(&self.received_raw)
+    }
+
+    /// Parses every request in the sent data, in order.
+    pub fn requests(&self) -> Result<Vec<HttpRequest>, HttpError> {
+        parse_requests(&self.sent_raw)
+    }
+
+    /// Parses every response in the received data, in order.
+    pub fn responses(&self) -> Result<Vec<HttpResponse>, HttpError> {
+        parse_responses(&self.received_raw)
+    }
+
+    /// Splits the transcript into its request and response pairs.
+    pub fn exchanges(&self) -> Result<Vec<HttpExchange>, HttpError> {
+        parse_exchanges(&self.sent_raw, &self.received_raw)
+    }
 }
 
 /// Custom serialization function for DateTime
@@ -0,0 +1,215 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use std::time::Duration;
+
+use crate::error::VerifierError;
+
+/// Default oldest acceptable proof, one day.
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Default tolerance for notary clocks running ahead of ours, five minutes.
+pub const DEFAULT_MAX_FUTURE_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// Default delay between a performer verifying a proof and a validator checking the task, one hour.
+pub const DEFAULT_MAX_TASK_LAG: Duration = Duration::from_secs(60 * 60);
+
+/// Longest window a policy should be configured with, a hundred years. Longer ones only come
+/// from mistakes, and close to the limits of [`DateTime`] no bound applies at all.
+pub const MAX_WINDOW: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// Rules a presentation's connection time must satisfy to be accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationPolicy {
+    /// How long ago the TLS connection may have been started
+    pub max_age: Duration,
+    /// How far in the future the connection time may be, to absorb clock skew
+    pub max_future_skew: Duration,
+    /// Connections started before this time are never accepted
+    pub min_time: Option<DateTime<Utc>>,
+    /// How long before the current time a task may claim its proof was verified
+    pub max_task_lag: Duration,
+    /// The time proofs are judged at, the current time when unset
+    pub reference_time: Option<DateTime<Utc>>,
+}
+
+impl Default for VerificationPolicy {
+    fn default() -> Self {
+        VerificationPolicy {
+            max_age: DEFAULT_MAX_AGE,
+            max_future_skew: DEFAULT_MAX_FUTURE_SKEW,
+            min_time: None,
+            max_task_lag: DEFAULT_MAX_TASK_LAG,
+            reference_time: None,
+        }
+    }
+}
+
+impl VerificationPolicy {
+    /// Returns a copy of the policy that judges proofs at `time` instead of the current time.
+    pub fn at(&self, time: DateTime<Utc>) -> Self {
+        VerificationPolicy {
+            reference_time: Some(time),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the policy that judges a task's proof when the performer verified it.
+    ///
+    /// Validators use this so both sides agree on whether the proof was stale. The performer
+    /// writes `verified_at` itself, so it is only trusted within [`Self::max_task_lag`] of the
+    /// current time, backdating a task can't make an old proof fresh again.
+    ///
+    /// # Arguments
+    ///
+    /// * `verified_at` - The time the task claims the proof was verified at.
+    pub fn for_task(&self, verified_at: DateTime<Utc>) -> Result<Self, VerifierError> {
+        let now = self.reference_time.unwrap_or_else(Utc::now);
+
+        if later(now, self.max_future_skew).is_some_and(|latest| verified_at > latest) {
+            return Err(VerifierError::FutureProof(format!(
+                "task verified at {} is more than {}s ahead of {}",
+                verified_at.to_rfc3339(),
+                self.max_future_skew.as_secs(),
+                now.to_rfc3339()
+            )));
+        }
+
+        if earlier(now, self.max_task_lag).is_some_and(|earliest| verified_at < earliest) {
+            return Err(VerifierError::StaleProof(format!(
+                "task verified at {} is more than {}s older than {}",
+                verified_at.to_rfc3339(),
+                self.max_task_lag.as_secs(),
+                now.to_rfc3339()
+            )));
+        }
+
+        Ok(self.at(verified_at))
+    }
+
+    /// Checks that a connection started at `time` is acceptable.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time at which the TLS connection was started.
+    pub fn check(&self, time: DateTime<Utc>) -> Result<(), VerifierError> {
+        let now = self.reference_time.unwrap_or_else(Utc::now);
+
+        if let Some(min_time) = self.min_time {
+            if time < min_time {
+                return Err(VerifierError::StaleProof(format!(
+                    "connection at {} is before the minimum time {}",
+                    time.to_rfc3339(),
+                    min_time.to_rfc3339()
+                )));
+            }
+        }
+
+        if later(now, self.max_future_skew).is_some_and(|latest| time > latest) {
+            return Err(VerifierError::FutureProof(format!(
+                "connection at {} is more than {}s ahead of {}",
+                time.to_rfc3339(),
+                self.max_future_skew.as_secs(),
+                now.to_rfc3339()
+            )));
+        }
+
+        if earlier(now, self.max_age).is_some_and(|earliest| time < earliest) {
+            return Err(VerifierError::StaleProof(format!(
+                "connection at {} is more than {}s older than {}",
+                time.to_rfc3339(),
+                self.max_age.as_secs(),
+                now.to_rfc3339()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+// None when the result is out of range, no bound applies then
+fn earlier(time: DateTime<Utc>, by: Duration) -> Option<DateTime<Utc>> {
+    time.checked_sub_signed(TimeDelta::from_std(by).ok()?)
+}
+
+fn later(time: DateTime<Utc>, by: Duration) -> Option<DateTime<Utc>> {
+    time.checked_add_signed(TimeDelta::from_std(by).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn policy() -> VerificationPolicy {
+        VerificationPolicy {
+            max_age: Duration::from_secs(60 * 60),
+            max_future_skew: Duration::from_secs(60),
+            min_time: Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+            max_task_lag: Duration::from_secs(10 * 60),
+            reference_time: Some(Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_accepts_fresh_proofs() {
+        let policy = policy();
+        assert!(policy
+            .check(Utc.with_ymd_and_hms(2025, 3, 1, 11, 30, 0).unwrap())
+            .is_ok());
+        assert!(policy
+            .check(Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 30).unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rejects_untimely_proofs() {
+        let policy = policy();
+
+        let too_old = policy.check(Utc.with_ymd_and_hms(2025, 3, 1, 10, 0, 0).unwrap());
+        assert!(matches!(too_old, Err(VerifierError::StaleProof(_))));
+
+        let from_future = policy.check(Utc.with_ymd_and_hms(2025, 3, 1, 12, 5, 0).unwrap());
+        assert!(matches!(from_future, Err(VerifierError::FutureProof(_))));
+
+        let before_min = policy
+            .at(Utc.with_ymd_and_hms(2025, 1, 1, 0, 30, 0).unwrap())
+            .check(Utc.with_ymd_and_hms(2024, 12, 31, 23, 59, 0).unwrap());
+        assert!(matches!(before_min, Err(VerifierError::StaleProof(_))));
+    }
+
+    #[test]
+    fn test_reference_time_is_respected() {
+        let time = Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap();
+        assert!(policy().check(time).is_err());
+        assert!(policy().at(time).check(time).is_ok());
+    }
+
+    #[test]
+    fn test_backdated_tasks_are_rejected() {
+        let policy = policy();
+        let notarized = Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap();
+
+        // a task claiming to be verified right after notarization can't pass hours later
+        let backdated = policy.for_task(Utc.with_ymd_and_hms(2025, 3, 1, 9, 1, 0).unwrap());
+        assert!(matches!(backdated, Err(VerifierError::StaleProof(_))));
+
+        let recent = Utc.with_ymd_and_hms(2025, 3, 1, 11, 55, 0).unwrap();
+        let task = policy.for_task(recent).unwrap();
+        assert_eq!(task.reference_time, Some(recent));
+        assert!(task.check(notarized).is_err());
+
+        let ahead = policy.for_task(Utc.with_ymd_and_hms(2025, 3, 1, 12, 5, 0).unwrap());
+        assert!(matches!(ahead, Err(VerifierError::FutureProof(_))));
+    }
+
+    #[test]
+    fn test_windows_never_overflow() {
+        let policy = VerificationPolicy {
+            max_age: Duration::from_secs(u64::MAX),
+            max_future_skew: Duration::from_secs(u64::MAX),
+            ..policy()
+        };
+        assert!(policy
+            .check(Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap())
+            .is_ok());
+    }
+}
@@ -0,0 +1,103 @@
+use serde::Serialize;
+use std::ops::Range;
+
+/// Byte ranges of one side of a transcript that were authenticated by the notary.
+///
+/// Every other byte was redacted by the prover and shows up as `~` in the transcript strings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AuthenticatedRanges {
+    ranges: Vec<Range<usize>>,
+    len: usize,
+}
+
+impl AuthenticatedRanges {
+    /// Creates the authenticated ranges of a transcript side.
+    ///
+    /// # Arguments
+    ///
+    /// * `ranges` - The authenticated ranges, in any order and possibly overlapping.
+    /// * `len` - The total length of the transcript side in bytes.
+    pub fn new(ranges: impl IntoIterator<Item = Range<usize>>, len: usize) -> Self {
+        let mut sorted: Vec<Range<usize>> = ranges
+            .into_iter()
+            .map(|range| range.start.min(len)..range.end.min(len))
+            .filter(|range| !range.is_empty())
+            .collect();
+        sorted.sort_by_key(|range| range.start);
+
+        let mut ranges: Vec<Range<usize>> = Vec::with_capacity(sorted.len());
+        for range in sorted {
+            match ranges.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => ranges.push(range),
+            }
+        }
+
+        AuthenticatedRanges { ranges, len }
+    }
+
+    /// Creates ranges covering a fully authenticated transcript side of `len` bytes.
+    pub fn full(len: usize) -> Self {
+        Self::new(std::iter::once(0..len), len)
+    }
+
+    /// Returns the merged authenticated ranges in ascending order.
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    /// Returns the total length of the transcript side in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks whether the transcript side is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Checks whether every byte in `range` was authenticated. Empty ranges are trivially
+    /// authenticated.
+    pub fn is_authenticated(&self, range: Range<usize>) -> bool {
+        if range.is_empty() {
+            return true;
+        }
+
+        // Ranges are merged, so a covered range must sit inside a single one of them.
+        self.ranges
+            .iter()
+            .any(|authed| authed.start <= range.start && range.end <= authed.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges_are_merged_and_clamped() {
+        let ranges = AuthenticatedRanges::new([10..20, 0..5, 4..8, 18..40], 30);
+        assert_eq!(ranges.ranges(), &[0..8, 10..30]);
+        assert_eq!(ranges.len(), 30);
+    }
+
+    #[test]
+    fn test_is_authenticated() {
+        let ranges = AuthenticatedRanges::new([0..5, 5..10, 15..20], 20);
+
+        assert!(ranges.is_authenticated(0..10));
+        assert!(ranges.is_authenticated(16..20));
+        assert!(ranges.is_authenticated(12..12));
+        assert!(!ranges.is_authenticated(8..16));
+        assert!(!ranges.is_authenticated(10..15));
+        assert!(!ranges.is_authenticated(18..25));
+    }
+
+    #[test]
+    fn test_full_ranges() {
+        let ranges = AuthenticatedRanges::full(42);
+        assert!(ranges.is_authenticated(0..42));
+        assert!(!ranges.is_authenticated(0..43));
+        assert!(AuthenticatedRanges::full(0).ranges().is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+use chrono::{DateTime, Utc};
+use elliptic_curve::{pkcs8::DecodePublicKey, sec1::ToEncodedPoint};
+use serde::Deserialize;
+
+use crate::error::VerifierError;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Label given to the notary key bundled with this crate.
+pub const BUNDLED_NOTARY_LABEL: &str = "bundled";
+
+/// Label given to a notary key configured inline as PEM.
+pub const INLINE_NOTARY_LABEL: &str = "inline";
+
+/// A notary public key trusted by this Verifier
+#[derive(Debug, Clone)]
+pub struct TrustedNotary {
+    /// Human readable name reported back when this notary signs a presentation
+    pub label: String,
+    /// The notary's P-256 public key
+    pub key: p256::PublicKey,
+    /// The key is not trusted for connections started before this time
+    pub not_before: Option<DateTime<Utc>>,
+    /// The key is not trusted for connections started after this time
+    pub not_after: Option<DateTime<Utc>>,
+}
+
+impl TrustedNotary {
+    /// Creates a notary entry from a PEM encoded public key, valid at any time.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - The name of the notary.
+    /// * `pem` - The PEM encoded P-256 public key.
+    pub fn from_pem(label: impl Into<String>, pem: &str) -> Result<Self, VerifierError> {
+        let label = label.into();
+        let key = p256::PublicKey::from_public_key_pem(pem.trim()).map_err(|e| {
+            VerifierError::TrustStore(format!("Invalid public key for notary '{}': {}", label, e))
+        })?;
+
+        Ok(TrustedNotary {
+            label,
+            key,
+            not_before: None,
+            not_after: None,
+        })
+    }
+
+    /// Checks whether `key_data` is this notary's key in SEC1 encoding.
+    pub fn matches(&self, key_data: &[u8]) -> bool {
+        self.key.to_encoded_point(true).as_bytes() == key_data
+            || self.key.to_encoded_point(false).as_bytes() == key_data
+    }
+
+    /// Checks whether the key may be trusted for a connection started at `time`.
+    pub fn is_valid_at(&self, time: DateTime<Utc>) -> bool {
+        self.not_before.is_none_or(|start| time >= start)
+            && self.not_after.is_none_or(|end| time <= end)
+    }
+}
+
+/// Set of notaries whose attestations are accepted by this Verifier
+#[derive(Debug, Clone, Default)]
+pub struct NotaryTrustStore {
+    notaries: Vec<TrustedNotary>,
+}
+
+/// On-disk description of a trust store
+#[derive(Deserialize)]
+struct TrustStoreManifest {
+    notaries: Vec<NotaryEntry>,
+}
+
+/// A single notary in a trust store manifest. The key is given either inline or as a path
+/// relative to the manifest.
+#[derive(Deserialize)]
+struct NotaryEntry {
+    label: String,
+    pem: Option<String>,
+    path: Option<PathBuf>,
+    not_before: Option<DateTime<Utc>>,
+    not_after: Option<DateTime<Utc>>,
+}
+
+impl NotaryTrustStore {
+    /// Creates a trust store from a list of notaries.
+    pub fn new(notaries: Vec<TrustedNotary>) -> Self {
+        NotaryTrustStore { notaries }
+    }
+
+    /// Returns a trust store holding only the notary key bundled with this crate.
+    pub fn bundled() -> Self {
+        let pem =
+            std::str::from_utf8(include_bytes!("../../../fixture/notary/notary.pub")).unwrap();
+        NotaryTrustStore::new(vec![
+            TrustedNotary::from_pem(BUNDLED_NOTARY_LABEL, pem).unwrap()
+        ])
+    }
+
+    /// Loads the trust store a setting describes.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A path to a directory or file, an inline JSON manifest or an inline PEM key.
+    pub fn from_setting(value: &str) -> Result<Self, VerifierError> {
+        let value = value.trim();
+        if value.starts_with('{') {
+            return Self::from_manifest(value, Path::new("."));
+        }
+        if value.starts_with("-----BEGIN") {
+            return Ok(NotaryTrustStore::new(vec![TrustedNotary::from_pem(
+                INLINE_NOTARY_LABEL,
+                value,
+            )?]));
+        }
+
+        let path = Path::new(value);
+        if path.is_dir() {
+            Self::from_dir(path)
+        } else {
+            Self::from_file(path)
+        }
+    }
+
+    /// Loads a trust store from a file. JSON files are read as manifests, anything else as a
+    /// single PEM key labelled with the file stem.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The manifest or PEM file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, VerifierError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| {
+            VerifierError::TrustStore(format!("Failed to read '{}': {}", path.display(), e))
+        })?;
+
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let base = path.parent().unwrap_or_else(|| Path::new("."));
+            return Self::from_manifest(&contents, base);
+        }
+
+        let label = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        Ok(NotaryTrustStore::new(vec![TrustedNotary::from_pem(
+            label, &contents,
+        )?]))
+    }
+
+    /// Loads every `.json` manifest and `.pub` or `.pem` key found directly in a directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to scan.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, VerifierError> {
+        let dir = dir.as_ref();
+        let mut paths = fs::read_dir(dir)
+            .map_err(|e| {
+                VerifierError::TrustStore(format!("Failed to read '{}': {}", dir.display(), e))
+            })?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext == "json" || ext == "pub" || ext == "pem")
+            })
+            .collect::<Vec<_>>();
+        // Keep the load order stable so labels are reported deterministically.
+        paths.sort();
+
+        let mut store = NotaryTrustStore::default();
+        for path in paths {
+            store.extend(Self::from_file(&path)?);
+        }
+
+        if store.is_empty() {
+            return Err(VerifierError::TrustStore(format!(
+                "No notary keys found in '{}'",
+                dir.display()
+            )));
+        }
+
+        Ok(store)
+    }
+
+    /// Parses a JSON trust store manifest.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The manifest contents.
+    /// * `base` - The directory relative key paths are resolved against.
+    pub fn from_manifest(json: &str, base: &Path) -> Result<Self, VerifierError> {
+        let manifest: TrustStoreManifest = serde_json::from_str(json).map_err(|e| {
+            VerifierError::TrustStore(format!("Invalid notary trust store manifest: {}", e))
+        })?;
+
+        let mut notaries = Vec::with_capacity(manifest.notaries.len());
+        for entry in manifest.notaries {
+            let pem = match (entry.pem, entry.path) {
+                (Some(pem), None) => pem,
+                (None, Some(path)) => {
+                    let path = base.join(path);
+                    fs::read_to_string(&path).map_err(|e| {
+                        VerifierError::TrustStore(format!(
+                            "Failed to read '{}': {}",
+                            path.display(),
+                            e
+                        ))
+                    })?
+                }
+                _ => {
+                    return Err(VerifierError::TrustStore(format!(
+                        "Notary '{}' must set exactly one of 'pem' or 'path'",
+                        entry.label
+                    )))
+                }
+            };
+
+            let mut notary = TrustedNotary::from_pem(entry.label, &pem)?;
+            notary.not_before = entry.not_before;
+            notary.not_after = entry.not_after;
+            notaries.push(notary);
+        }
+
+        Ok(NotaryTrustStore::new(notaries))
+    }
+
+    /// Adds a notary to the store.
+    pub fn add(&mut self, notary: TrustedNotary) {
+        self.notaries.push(notary);
+    }
+
+    /// Moves every notary of `other` into this store.
+    pub fn extend(&mut self, other: NotaryTrustStore) {
+        self.notaries.extend(other.notaries);
+    }
+
+    /// Returns the notaries in this store.
+    pub fn notaries(&self) -> &[TrustedNotary] {
+        &self.notaries
+    }
+
+    /// Checks whether the store holds no notaries.
+    pub fn is_empty(&self) -> bool {
+        self.notaries.is_empty()
+    }
+
+    /// Returns every notary whose key is `key_data`, regardless of validity window.
+    pub fn find_by_key(&self, key_data: &[u8]) -> Vec<&TrustedNotary> {
+        self.notaries
+            .iter()
+            .filter(|notary| notary.matches(key_data))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const NOTARY_PEM: &str = include_str!("../../../fixture/notary/notary.pub");
+
+    #[test]
+    fn test_bundled_key_matches_sec1_encodings() {
+        let store = NotaryTrustStore::bundled();
+        let key = store.notaries()[0].key;
+
+        let compressed = key.to_encoded_point(true);
+        let uncompressed = key.to_encoded_point(false);
+        assert_eq!(store.find_by_key(compressed.as_bytes()).len(), 1);
+        assert_eq!(store.find_by_key(uncompressed.as_bytes()).len(), 1);
+        assert!(store.find_by_key(&[0u8; 33]).is_empty());
+    }
+
+    #[test]
+    fn test_manifest_validity_window() {
+        let manifest = serde_json::json!({
+            "notaries": [{
+                "label": "rotating",
+                "pem": NOTARY_PEM,
+                "not_before": "2025-01-01T00:00:00Z",
+                "not_after": "2025-06-30T23:59:59Z"
+            }]
+        })
+        .to_string();
+
+        let store = NotaryTrustStore::from_manifest(&manifest, Path::new(".")).unwrap();
+        let notary = &store.notaries()[0];
+        assert_eq!(notary.label, "rotating");
+        assert!(notary.is_valid_at(Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap()));
+        assert!(!notary.is_valid_at(Utc.with_ymd_and_hms(2024, 12, 31, 0, 0, 0).unwrap()));
+        assert!(!notary.is_valid_at(Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_manifest_rejects_ambiguous_entries() {
+        let both = serde_json::json!({
+            "notaries": [{ "label": "both", "pem": NOTARY_PEM, "path": "notary.pub" }]
+        })
+        .to_string();
+        assert!(NotaryTrustStore::from_manifest(&both, Path::new(".")).is_err());
+
+        let neither = r#"{"notaries": [{ "label": "neither" }]}"#;
+        assert!(NotaryTrustStore::from_manifest(neither, Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn test_dir_loads_keys_and_manifests() {
+        let dir = std::env::temp_dir().join(format!("notary-trust-store-{}", std::process::id()));
+        fs::create_dir_all(dir.join("keys")).unwrap();
+        fs::write(dir.join("current.pub"), NOTARY_PEM).unwrap();
+        fs::write(dir.join("keys/next.pem"), NOTARY_PEM).unwrap();
+        fs::write(
+            dir.join("rotation.json"),
+            r#"{"notaries": [{ "label": "next", "path": "keys/next.pem" }]}"#,
+        )
+        .unwrap();
+
+        let store = NotaryTrustStore::from_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let labels: Vec<_> = store.notaries().iter().map(|n| n.label.as_str()).collect();
+        assert_eq!(labels, vec!["current", "next"]);
+    }
+}
@@ -0,0 +1,187 @@
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use verifier::VerificationResult;
+
+use crate::{
+    error::MainProcessError::{self, BadContentSchema},
+    transform::Transforms,
+    utils::{extract_value, value_to_string},
+};
+
+/// A condition the transcript must meet before anything is registered
+///
+/// Declared in a schema as e.g. `{"key": "received@status", "equals": 200}` or
+/// `{"key": "sent@path", "matches": "^/2/tweets/\\d+$"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Assertion {
+    /// Content key of the value being checked
+    pub key: String,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    /// The value equals this JSON value, numbers and booleans also match their text form
+    Equals(Value),
+    NotEquals(Value),
+    /// The value matches this regular expression somewhere, anchor it to match it whole
+    Matches(String),
+}
+
+impl Assertion {
+    /// Evaluates the assertion, returning why it failed.
+    ///
+    /// # Arguments
+    ///
+    /// * `transcript` - The verified transcript.
+    /// * `transforms` - The schema's transforms, applied before the value is checked.
+    pub fn evaluate(
+        &self,
+        transcript: &VerificationResult,
+        transforms: &Transforms,
+    ) -> Result<(), String> {
+        let actual = extract_value(transcript, &self.key, transforms)
+            .map_err(|e| format!("'{}' could not be read: {}", self.key, e))?;
+
+        let holds = match &self.check {
+            Check::Equals(expected) => loosely_equal(&actual, expected),
+            Check::NotEquals(expected) => !loosely_equal(&actual, expected),
+            Check::Matches(pattern) => Regex::new(pattern)
+                .map_err(|e| format!("'{}' has an invalid pattern: {}", self.key, e))?
+                .is_match(&value_to_string(&actual)),
+        };
+        if holds {
+            return Ok(());
+        }
+
+        let expectation = match &self.check {
+            Check::Equals(expected) => format!("expected {}", expected),
+            Check::NotEquals(expected) => format!("expected anything but {}", expected),
+            Check::Matches(pattern) => format!("expected to match '{}'", pattern),
+        };
+        Err(format!("'{}' is {}, {}", self.key, actual, expectation))
+    }
+}
+
+/// Evaluates every assertion, reporting all failures at once.
+///
+/// # Arguments
+///
+/// * `transcript` - The verified transcript.
+/// * `assertions` - The assertions declared by the schema.
+/// * `transforms` - The schema's transforms.
+pub fn check_assertions(
+    transcript: &VerificationResult,
+    assertions: &[Assertion],
+    transforms: &Transforms,
+) -> Result<(), MainProcessError> {
+    let failures: Vec<String> = assertions
+        .iter()
+        .filter_map(|assertion| assertion.evaluate(transcript, transforms).err())
+        .collect();
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(BadContentSchema(format!(
+            "Schema assertions failed: {}",
+            failures.join("; ")
+        )))
+    }
+}
+
+// extracted values are text unless a transform typed them, so `"200"` equals `200`
+fn loosely_equal(actual: &Value, expected: &Value) -> bool {
+    let actual = match (actual, expected) {
+        (Value::String(text), Value::String(_)) => Value::String(text.clone()),
+        (Value::String(text), _) => match serde_json::from_str(text.trim()) {
+            Ok(parsed) => parsed,
+            Err(_) => return false,
+        },
+        (other, _) => other.clone(),
+    };
+
+    match (&actual, expected) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => &actual == expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use tlsn_core::connection::ServerName;
+    use verifier::AuthenticatedRanges;
+
+    use super::*;
+
+    fn transcript(received: &str) -> VerificationResult {
+        let sent =
+            "GET /2/users/by/username/forge?expansions=pinned HTTP/1.1\r\nHost: api.x.com\r\n\r\n";
+        VerificationResult {
+            server_name: ServerName::new("api.x.com".to_string()),
+            time: Utc.with_ymd_and_hms(2024, 9, 19, 12, 23, 10).unwrap(),
+            notary: "test".to_string(),
+            sent_data: sent.to_string(),
+            received_data: received.to_string(),
+            sent_authed: AuthenticatedRanges::full(sent.len()),
+            received_authed: AuthenticatedRanges::full(received.len()),
+            sent_raw: sent.as_bytes().to_vec(),
+            received_raw: received.as_bytes().to_vec(),
+        }
+    }
+
+    fn assertions(json: &str) -> Vec<Assertion> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_assertions_hold() {
+        let transcript = transcript(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"data\":{\"verified\":true,\"followers\":1.0,\"name\":\"Forge\"}}",
+        );
+        let assertions = assertions(
+            r#"[
+                {"key": "received@status", "equals": 200},
+                {"key": "received@reason", "equals": "OK"},
+                {"key": "received>data.verified", "equals": true},
+                {"key": "received>data.followers", "equals": 1},
+                {"key": "received>data.name", "not_equals": "Someone"},
+                {"key": "sent@path", "matches": "^/2/users/by/username/[a-z]+$"}
+            ]"#,
+        );
+
+        assert!(check_assertions(&transcript, &assertions, &Transforms::new()).is_ok());
+    }
+
+    #[test]
+    fn test_failed_assertions_are_listed() {
+        let transcript = transcript(
+            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\n\r\n{\"errors\":[{\"title\":\"Not Found Error\"}]}",
+        );
+        let assertions = assertions(
+            r#"[
+                {"key": "received@status", "equals": 200},
+                {"key": "received>data.verified", "equals": true},
+                {"key": "sent@path", "matches": "^/2/tweets/"},
+                {"key": "sent@method", "equals": "GET"}
+            ]"#,
+        );
+
+        let error = check_assertions(&transcript, &assertions, &Transforms::new())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("'received@status' is \"404\", expected 200"));
+        assert!(error.contains("'received>data.verified' could not be read"));
+        assert!(error.contains("expected to match '^/2/tweets/'"));
+        assert!(!error.contains("sent@method"));
+
+        let invalid = self::assertions(r#"[{"key": "sent@path", "matches": "("}]"#);
+        assert!(check_assertions(&transcript, &invalid, &Transforms::new()).is_err());
+        assert!(serde_json::from_str::<Assertion>(r#"{"key": "sent@path"}"#).is_err());
+    }
+}
@@ -0,0 +1,605 @@
+use alloy::primitives::Address;
+use chrono::DateTime;
+use othentic::OthenticConfig;
+use pinata::PinataConfig;
+use registrar::RegistrarConfig;
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use verifier::{NotaryTrustStore, VerificationPolicy, MAX_WINDOW};
+
+use crate::utils::SensitiveFields;
+
+/// Environment variable pointing at the TOML config file.
+pub const CONFIG_FILE_ENV: &str = "FORGE_CONFIG";
+
+/// Config file read when [`CONFIG_FILE_ENV`] isn't set, settings come from the environment alone
+/// if it doesn't exist.
+pub const DEFAULT_CONFIG_FILE: &str = "forge.toml";
+
+/// Gateway IPFS URLs are built with when none is configured.
+pub const DEFAULT_IPFS_GATEWAY: &str = "https://ipfs.io/ipfs/";
+
+/// EigenDA disperser used when none is configured.
+pub const DEFAULT_EIGENDA_ENDPOINT: &str = "https://disperser-holesky.eigenda.xyz:443";
+
+/// Story RPC used when none is configured.
+pub const DEFAULT_STORY_RPC_URL: &str = "https://aeneid.storyrpc.io";
+
+/// Directory content schemas are loaded from when none is configured, if it exists.
+pub const DEFAULT_SCHEMA_DIR: &str = "schemas";
+
+/// Directory execution nodes persist registration jobs in when none is configured.
+pub const DEFAULT_JOB_STORE: &str = "jobs";
+
+/// Directory validation nodes persist the presentation index in when none is configured.
+pub const DEFAULT_PRESENTATION_INDEX: &str = "presentations";
+
+// every setting as its key in the config file and the environment variable overriding it
+const SETTINGS: [(&str, &str); 18] = [
+    ("listen_address", "LISTEN_ADDRESS"),
+    ("ipfs_gateway", "IPFS_GATEWAY"),
+    ("private_key", "PRIVATE_KEY"),
+    ("schema_dir", "SCHEMA_DIR"),
+    ("sensitive_fields", "SENSITIVE_FIELDS"),
+    ("job_store", "JOB_STORE"),
+    ("presentation_index", "PRESENTATION_INDEX"),
+    ("notary.trust_store", "NOTARY_TRUST_STORE"),
+    ("proofs.max_age_secs", "PROOF_MAX_AGE_SECS"),
+    ("proofs.max_future_skew_secs", "PROOF_MAX_FUTURE_SKEW_SECS"),
+    ("proofs.min_time", "PROOF_MIN_TIME"),
+    ("proofs.max_task_lag_secs", "PROOF_MAX_TASK_LAG_SECS"),
+    ("story.rpc_url", "STORY_RPC_URL"),
+    ("story.proxy_address", "PROXY_ADDRESS"),
+    ("eigenda.endpoint", "EIGENDA_ENDPOINT"),
+    ("pinata.api_key", "PINATA_API_KEY"),
+    ("pinata.api_secret", "PINATA_API_SECRET"),
+    ("othentic.rpc_url", "OTHENTIC_CLIENT_RPC_ADDRESS"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read the config file {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid configuration: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+}
+
+/// The binary a config is loaded for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    Execution,
+    Validation,
+}
+
+impl Service {
+    fn default_listen_address(&self) -> SocketAddr {
+        match self {
+            Service::Execution => SocketAddr::from(([0, 0, 0, 0], 2077)),
+            Service::Validation => SocketAddr::from(([0, 0, 0, 0], 2078)),
+        }
+    }
+}
+
+/// Settings of the execution and validation nodes, read from a TOML file and the environment
+#[derive(Debug, Clone)]
+pub struct ForgeConfig {
+    /// Address the HTTP server listens on
+    pub listen_address: SocketAddr,
+    /// Gateway IPFS URLs are built with, always ending with a slash
+    pub ipfs_gateway: String,
+    /// EigenDA disperser proofs of task are published to and read from
+    pub eigenda_endpoint: String,
+    /// Story RPC registrations are sent to and read from
+    pub story_rpc_url: String,
+    /// Notaries whose attestations are accepted, the bundled key when none is configured
+    pub trust_store: NotaryTrustStore,
+    /// Freshness rules proofs are judged by, keep them the same on every node
+    pub policy: VerificationPolicy,
+    /// Directory content schemas are loaded from, the registry is empty without one
+    pub schema_dir: Option<PathBuf>,
+    /// Fields no schema may read and no metadata may carry
+    pub sensitive: SensitiveFields,
+    /// Directory registration jobs are persisted in, only execution nodes keep one
+    pub job_store: PathBuf,
+    /// Directory the presentation index is persisted in, only validation nodes keep one
+    pub presentation_index: PathBuf,
+    // every setting that was set, for the ones only the execution node needs
+    settings: Settings,
+}
+
+/// Settings of the adapters only the execution node calls
+#[derive(Debug, Clone)]
+pub struct ExecutionConfig {
+    pub registrar: RegistrarConfig,
+    pub pinata: PinataConfig,
+    pub othentic: OthenticConfig,
+}
+
+impl ForgeConfig {
+    /// Reads the file named by [`CONFIG_FILE_ENV`], or [`DEFAULT_CONFIG_FILE`] when it exists,
+    /// then applies the environment variables overriding it.
+    ///
+    /// # Arguments
+    ///
+    /// * `service` - The binary loading it, which decides the default listen address.
+    pub fn load(service: Service) -> Result<Self, ConfigError> {
+        let path = match std::env::var(CONFIG_FILE_ENV) {
+            Ok(path) if !path.is_empty() => Some(path),
+            _ if Path::new(DEFAULT_CONFIG_FILE).is_file() => Some(DEFAULT_CONFIG_FILE.to_string()),
+            _ => None,
+        };
+        let file = match &path {
+            Some(path) => {
+                Some(
+                    std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+                        path: path.clone(),
+                        source,
+                    })?,
+                )
+            }
+            None => None,
+        };
+
+        Self::from_sources(file.as_deref(), service, |name| std::env::var(name).ok())
+    }
+
+    /// Builds the config from a TOML document and the environment variables overriding it.
+    ///
+    /// Every problem is listed at once, each naming the key and the variable that set it.
+    ///
+    /// # Arguments
+    ///
+    /// * `toml` - The config file, if there is one.
+    /// * `service` - The binary loading it, which decides the default listen address.
+    /// * `env` - Looks up an environment variable, empty values count as unset.
+    pub fn from_sources(
+        toml: Option<&str>,
+        service: Service,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let settings = Settings::read(toml, env)?;
+        let mut errors = Vec::new();
+
+        let listen_address = settings
+            .parse("listen_address", &mut errors, |value| {
+                SocketAddr::from_str(value)
+                    .map_err(|_| format!("'{}' is not an address like 0.0.0.0:2077", value))
+            })
+            .unwrap_or(service.default_listen_address());
+        let mut ipfs_gateway = settings
+            .parse("ipfs_gateway", &mut errors, check_url)
+            .unwrap_or_else(|| DEFAULT_IPFS_GATEWAY.to_string());
+        if !ipfs_gateway.ends_with('/') {
+            ipfs_gateway.push('/');
+        }
+        let eigenda_endpoint = settings
+            .parse("eigenda.endpoint", &mut errors, check_url)
+            .unwrap_or_else(|| DEFAULT_EIGENDA_ENDPOINT.to_string());
+        let story_rpc_url = settings
+            .parse("story.rpc_url", &mut errors, check_url)
+            .unwrap_or_else(|| DEFAULT_STORY_RPC_URL.to_string());
+        let trust_store = settings
+            .parse("notary.trust_store", &mut errors, |value| {
+                NotaryTrustStore::from_setting(value).map_err(|e| e.to_string())
+            })
+            .unwrap_or_else(NotaryTrustStore::bundled);
+        let defaults = VerificationPolicy::default();
+        let policy = VerificationPolicy {
+            max_age: settings
+                .parse("proofs.max_age_secs", &mut errors, parse_window)
+                .unwrap_or(defaults.max_age),
+            max_future_skew: settings
+                .parse("proofs.max_future_skew_secs", &mut errors, parse_window)
+                .unwrap_or(defaults.max_future_skew),
+            min_time: settings.parse("proofs.min_time", &mut errors, |value| {
+                DateTime::parse_from_rfc3339(value.trim())
+                    .map(|time| time.to_utc())
+                    .map_err(|e| format!("'{}' is not an RFC 3339 timestamp: {}", value, e))
+            }),
+            max_task_lag: settings
+                .parse("proofs.max_task_lag_secs", &mut errors, parse_window)
+                .unwrap_or(defaults.max_task_lag),
+            reference_time: None,
+        };
+        // a configured schema directory has to exist, the default one is optional
+        let schema_dir = settings
+            .parse("schema_dir", &mut errors, |value| {
+                let dir = PathBuf::from(value);
+                if dir.is_dir() {
+                    Ok(dir)
+                } else {
+                    Err(format!("'{}' is not a directory", value))
+                }
+            })
+            .or_else(|| {
+                Path::new(DEFAULT_SCHEMA_DIR)
+                    .is_dir()
+                    .then(|| PathBuf::from(DEFAULT_SCHEMA_DIR))
+            });
+        let sensitive = settings
+            .parse("sensitive_fields", &mut errors, |value| {
+                SensitiveFields::default()
+                    .extended(value)
+                    .map_err(|e| e.to_string())
+            })
+            .unwrap_or_default();
+        let job_store = PathBuf::from(settings.get("job_store").unwrap_or(DEFAULT_JOB_STORE));
+        let presentation_index = PathBuf::from(
+            settings
+                .get("presentation_index")
+                .unwrap_or(DEFAULT_PRESENTATION_INDEX),
+        );
+
+        // validation nodes hold no credentials, only the execution node's are checked
+        let config = ForgeConfig {
+            listen_address,
+            ipfs_gateway,
+            eigenda_endpoint,
+            story_rpc_url,
+            trust_store,
+            policy,
+            schema_dir,
+            sensitive,
+            job_store,
+            presentation_index,
+            settings,
+        };
+        if service == Service::Execution {
+            if let Err(ConfigError::Invalid(execution)) = config.execution() {
+                errors.extend(execution);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    /// Returns the settings of the registrar, Pinata and Othentic adapters, which only the
+    /// execution node calls.
+    pub fn execution(&self) -> Result<ExecutionConfig, ConfigError> {
+        let settings = &self.settings;
+        let mut errors = Vec::new();
+
+        // the batcher wallet both registers the IP and signs the task
+        let private_key = settings.require("private_key", &mut errors, |value| {
+            let hex = value.strip_prefix("0x").unwrap_or(value);
+            if hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                Ok(hex.to_string())
+            } else {
+                Err("must be 32 bytes of hex".to_string())
+            }
+        });
+        let proxy_address = settings.require("story.proxy_address", &mut errors, |value| {
+            Address::from_str(value).map_err(|_| format!("'{}' is not an address", value))
+        });
+        let api_key =
+            settings.require("pinata.api_key", &mut errors, |value| Ok(value.to_string()));
+        let api_secret = settings.require("pinata.api_secret", &mut errors, |value| {
+            Ok(value.to_string())
+        });
+        let othentic_rpc_url = settings.require("othentic.rpc_url", &mut errors, check_url);
+
+        match (
+            private_key,
+            proxy_address,
+            api_key,
+            api_secret,
+            othentic_rpc_url,
+        ) {
+            (
+                Some(private_key),
+                Some(proxy_address),
+                Some(api_key),
+                Some(api_secret),
+                Some(othentic_rpc_url),
+            ) if errors.is_empty() => Ok(ExecutionConfig {
+                registrar: RegistrarConfig {
+                    rpc_url: self.story_rpc_url.clone(),
+                    private_key: private_key.clone(),
+                    proxy_address,
+                },
+                pinata: PinataConfig {
+                    api_key,
+                    api_secret,
+                },
+                othentic: OthenticConfig {
+                    private_key,
+                    rpc_url: othentic_rpc_url,
+                },
+            }),
+            _ => Err(ConfigError::Invalid(errors)),
+        }
+    }
+}
+
+// the value of every setting that was set, environment variables taking precedence
+#[derive(Debug, Clone, Default)]
+struct Settings(BTreeMap<&'static str, String>);
+
+impl Settings {
+    fn read(toml: Option<&str>, env: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut values = BTreeMap::new();
+        let mut errors = Vec::new();
+
+        if let Some(toml) = toml {
+            let table: toml::Table = toml
+                .parse()
+                .map_err(|e| ConfigError::Invalid(vec![format!("config file: {}", e)]))?;
+            flatten("", &table, &mut values, &mut errors);
+        }
+        for (key, variable) in SETTINGS {
+            if let Some(value) = env(variable).filter(|value| !value.is_empty()) {
+                values.insert(key, value);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Settings(values))
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    // parses a setting if it was set, keeping why it is invalid
+    fn parse<T>(
+        &self,
+        key: &'static str,
+        errors: &mut Vec<String>,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Option<T> {
+        let value = self.get(key)?;
+        parse(value)
+            .map_err(|reason| errors.push(format!("{} ({}): {}", key, variable(key), reason)))
+            .ok()
+    }
+
+    fn require<T>(
+        &self,
+        key: &'static str,
+        errors: &mut Vec<String>,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Option<T> {
+        if self.get(key).is_none() {
+            errors.push(format!("{} ({}): is not set", key, variable(key)));
+        }
+        self.parse(key, errors, parse)
+    }
+}
+
+// collects the scalar values of a table under their dotted keys, refusing anything unknown
+fn flatten(
+    prefix: &str,
+    table: &toml::Table,
+    values: &mut BTreeMap<&'static str, String>,
+    errors: &mut Vec<String>,
+) {
+    for (name, value) in table {
+        let path = format!("{}{}", prefix, name);
+        // numbers and dates are read back from their text like environment variables are
+        let value = match value {
+            toml::Value::Table(table) => {
+                flatten(&format!("{}.", path), table, values, errors);
+                continue;
+            }
+            toml::Value::String(value) => value.clone(),
+            toml::Value::Integer(value) => value.to_string(),
+            toml::Value::Datetime(value) => value.to_string(),
+            _ => {
+                errors.push(format!("{}: must be a string, a number or a date", path));
+                continue;
+            }
+        };
+        match SETTINGS.iter().find(|(key, _)| *key == path) {
+            Some((key, _)) => {
+                values.insert(key, value);
+            }
+            None => errors.push(format!("{}: unknown setting", path)),
+        }
+    }
+}
+
+fn variable(key: &str) -> &'static str {
+    SETTINGS
+        .iter()
+        .find(|(setting, _)| *setting == key)
+        .map(|(_, variable)| *variable)
+        .unwrap_or_default()
+}
+
+// a number of seconds short enough to judge proofs with
+fn parse_window(value: &str) -> Result<Duration, String> {
+    let window = value
+        .trim()
+        .parse()
+        .map(Duration::from_secs)
+        .map_err(|_| format!("'{}' is not a number of seconds", value))?;
+    if window > MAX_WINDOW {
+        return Err(format!("must be at most {}s", MAX_WINDOW.as_secs()));
+    }
+    Ok(window)
+}
+
+fn check_url(value: &str) -> Result<String, String> {
+    match value.split_once("://") {
+        Some(("http" | "https", rest)) if !rest.is_empty() => Ok(value.to_string()),
+        _ => Err(format!("'{}' is not an http(s) URL", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIVATE_KEY: &str = "0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d";
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn test_file_settings_are_overridden_by_the_environment() {
+        let toml = r#"
+            ipfs_gateway = "https://gateway.pinata.cloud/ipfs"
+            private_key = "0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d"
+
+            [story]
+            rpc_url = "https://story.example"
+            proxy_address = "0xF9FD6e4b853DE1182c31d39EC904175bcE945853"
+
+            [pinata]
+            api_key = "key"
+            api_secret = "secret"
+
+            [othentic]
+            rpc_url = "http://aggregator:8545"
+        "#;
+        let config = ForgeConfig::from_sources(
+            Some(toml),
+            Service::Execution,
+            env(&[
+                ("STORY_RPC_URL", "https://aeneid.storyrpc.io"),
+                ("LISTEN_ADDRESS", ""),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.listen_address.port(), 2077);
+        assert_eq!(config.ipfs_gateway, "https://gateway.pinata.cloud/ipfs/");
+        assert_eq!(config.eigenda_endpoint, DEFAULT_EIGENDA_ENDPOINT);
+        assert_eq!(config.story_rpc_url, "https://aeneid.storyrpc.io");
+
+        let execution = config.execution().unwrap();
+        assert_eq!(execution.registrar.rpc_url, "https://aeneid.storyrpc.io");
+        assert_eq!(execution.othentic.private_key, &PRIVATE_KEY[2..]);
+        assert_eq!(execution.pinata.api_secret, "secret");
+    }
+
+    #[test]
+    fn test_validation_needs_no_credentials() {
+        let config = ForgeConfig::from_sources(None, Service::Validation, env(&[])).unwrap();
+        assert_eq!(config.listen_address.port(), 2078);
+        assert_eq!(config.ipfs_gateway, DEFAULT_IPFS_GATEWAY);
+        assert!(config.execution().is_err());
+
+        // the example file only leaves out credentials
+        let example = include_str!("../forge.example.toml");
+        let config =
+            ForgeConfig::from_sources(Some(example), Service::Validation, env(&[])).unwrap();
+        assert_eq!(config.eigenda_endpoint, DEFAULT_EIGENDA_ENDPOINT);
+    }
+
+    #[test]
+    fn test_every_problem_is_listed() {
+        let error = ForgeConfig::from_sources(
+            Some("listen_address = \"localhost\"\n[pinata]\napi_key = \"key\"\nsecret = \"x\""),
+            Service::Execution,
+            env(&[
+                ("PRIVATE_KEY", "0x1234"),
+                ("EIGENDA_ENDPOINT", "disperser:443"),
+            ]),
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(error.contains("pinata.secret: unknown setting"));
+
+        let error = ForgeConfig::from_sources(
+            Some("listen_address = \"localhost\""),
+            Service::Execution,
+            env(&[
+                ("PRIVATE_KEY", "0x1234"),
+                ("EIGENDA_ENDPOINT", "disperser:443"),
+            ]),
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(error.contains("listen_address (LISTEN_ADDRESS): 'localhost' is not an address"));
+        assert!(error.contains("eigenda.endpoint (EIGENDA_ENDPOINT): 'disperser:443'"));
+        assert!(error.contains("private_key (PRIVATE_KEY): must be 32 bytes of hex"));
+        assert!(error.contains("story.proxy_address (PROXY_ADDRESS): is not set"));
+        assert!(error.contains("othentic.rpc_url (OTHENTIC_CLIENT_RPC_ADDRESS): is not set"));
+
+        // windows too large to compare times with are refused up front
+        let error = ForgeConfig::from_sources(
+            None,
+            Service::Validation,
+            env(&[
+                ("PROOF_MAX_AGE_SECS", "18446744073709551615"),
+                ("PROOF_MIN_TIME", "yesterday"),
+                ("NOTARY_TRUST_STORE", "/nonexistent/notaries"),
+                ("SCHEMA_DIR", "/nonexistent/schemas"),
+                ("SENSITIVE_FIELDS", "{\"secrets\": []}"),
+            ]),
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(error.contains("proofs.max_age_secs (PROOF_MAX_AGE_SECS): must be at most"));
+        assert!(error.contains("proofs.min_time (PROOF_MIN_TIME): 'yesterday' is not an RFC 3339"));
+        assert!(error.contains("notary.trust_store (NOTARY_TRUST_STORE): "));
+        assert!(
+            error.contains("schema_dir (SCHEMA_DIR): '/nonexistent/schemas' is not a directory")
+        );
+        assert!(error.contains("sensitive_fields (SENSITIVE_FIELDS): "));
+    }
+
+    #[test]
+    fn test_node_settings_are_typed() {
+        let toml = r#"
+            job_store = "/var/lib/forge/jobs"
+
+            [proofs]
+            max_age_secs = 600
+            min_time = 2025-01-01T00:00:00Z
+        "#;
+        let config = ForgeConfig::from_sources(
+            Some(toml),
+            Service::Validation,
+            env(&[("PROOF_MAX_TASK_LAG_SECS", "60"), ("JOB_STORE", "")]),
+        )
+        .unwrap();
+
+        assert_eq!(config.policy.max_age, Duration::from_secs(600));
+        assert_eq!(config.policy.max_task_lag, Duration::from_secs(60));
+        assert_eq!(
+            config.policy.max_future_skew,
+            VerificationPolicy::default().max_future_skew
+        );
+        assert_eq!(
+            config.policy.min_time.unwrap().to_rfc3339(),
+            "2025-01-01T00:00:00+00:00"
+        );
+        assert_eq!(config.job_store, PathBuf::from("/var/lib/forge/jobs"));
+        assert_eq!(
+            config.presentation_index,
+            PathBuf::from(DEFAULT_PRESENTATION_INDEX)
+        );
+        assert_eq!(
+            config.trust_store.notaries()[0].label,
+            verifier::BUNDLED_NOTARY_LABEL
+        );
+        // tests run from the crate, which ships the default schema directory
+        assert_eq!(config.schema_dir, Some(PathBuf::from(DEFAULT_SCHEMA_DIR)));
+    }
+}
@@ -3,6 +3,7 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use eyre::Report;
+use verifier::VerifierError;
 // use std::error::Error;
 
 #[derive(Debug, thiserror::Error)]
@@ -17,13 +18,28 @@ pub enum MainProcessError {
     BadRequest(String),
 
     #[error("Invalid transcript proof: {0}")]
-    BadTranscriptProof(String),
+    BadTranscriptProof(VerifierError),
 
     #[error("Invalid content schema: {0}")]
     BadContentSchema(String),
 
     #[error("Failed to upload file: {0}")]
     BadFileUse(String),
+
+    #[error("Unknown content schema: {0}")]
+    UnknownSchema(String),
+
+    #[error("Unknown job: {0}")]
+    UnknownJob(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("On-chain registration failed: {0}")]
+    Registration(String),
 }
 
 impl MainProcessError {
@@ -35,6 +51,30 @@ impl MainProcessError {
                 StatusCode::BAD_REQUEST
             }
             Self::BadFileUse(_) => StatusCode::EXPECTATION_FAILED,
+            Self::UnknownSchema(_) | Self::UnknownJob(_) => StatusCode::NOT_FOUND,
+            Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Registration(_) => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// Checks whether trying again may succeed, e.g. after an upload or an RPC call failed.
+    ///
+    /// A failed registration isn't, its transaction may still land.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Unexpected(_) | Self::BadFileUse(_) | Self::Timeout(_)
+        )
+    }
+}
+
+impl From<VerifierError> for MainProcessError {
+    fn from(e: VerifierError) -> Self {
+        match e {
+            // a presentation that decoded but can't be encoded again isn't the request's fault
+            VerifierError::Serialization(_) => Self::Unexpected(Report::new(e)),
+            e => Self::BadTranscriptProof(e),
         }
     }
 }
@@ -0,0 +1,644 @@
+use alloy::primitives::{keccak256, FixedBytes};
+use chrono::{DateTime, Utc};
+use eyre::Report;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use sled::{
+    transaction::{ConflictableTransactionError, TransactionError},
+    Transactional,
+};
+use std::{fmt, path::Path};
+use uuid::Uuid;
+use verifier::{decode_presentation, encode_presentation, VerifierError};
+
+use crate::error::MainProcessError::{self, Conflict, UnknownJob};
+
+/// A step of a registration, listed in the order they run
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, utoipa::ToSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum StageName {
+    Verify,
+    Extract,
+    UploadMedia,
+    UploadMetadata,
+    RegisterOnchain,
+    PublishDa,
+    SubmitTask,
+}
+
+impl StageName {
+    /// Every stage in the order they run
+    pub const ALL: [StageName; 7] = [
+        StageName::Verify,
+        StageName::Extract,
+        StageName::UploadMedia,
+        StageName::UploadMetadata,
+        StageName::RegisterOnchain,
+        StageName::PublishDa,
+        StageName::SubmitTask,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StageName::Verify => "verify",
+            StageName::Extract => "extract",
+            StageName::UploadMedia => "upload-media",
+            StageName::UploadMetadata => "upload-metadata",
+            StageName::RegisterOnchain => "register-onchain",
+            StageName::PublishDa => "publish-da",
+            StageName::SubmitTask => "submit-task",
+        }
+    }
+
+    /// Checks whether running the stage again after an interruption is harmless. Pinning the
+    /// same content or dispersing the same blob twice is, registering the IP twice isn't.
+    pub fn is_repeatable(&self) -> bool {
+        !matches!(self, StageName::RegisterOnchain | StageName::SubmitTask)
+    }
+}
+
+impl fmt::Display for StageName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StageStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    /// Done, then undone because a later stage failed
+    Compensated,
+}
+
+/// Progress of a single stage
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct StageState {
+    pub stage: StageName,
+    pub status: StageStatus,
+    /// What the stage produced, e.g. IPFS URLs or the transaction hash
+    #[schema(value_type = Object)]
+    pub output: Option<Value>,
+    /// Why the last try failed
+    pub error: Option<String>,
+    /// Tries so far, transient failures are retried
+    #[serde(default)]
+    pub attempts: u32,
+    #[schema(value_type = String, format = DateTime)]
+    pub started_at: Option<DateTime<Utc>>,
+    #[schema(value_type = String, format = DateTime)]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl StageState {
+    fn new(stage: StageName) -> Self {
+        StageState {
+            stage,
+            status: StageStatus::Pending,
+            output: None,
+            error: None,
+            attempts: 0,
+            started_at: None,
+            finished_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    /// Registered on chain but a later stage failed, retried when the node restarts
+    Stalled,
+}
+
+/// A registration running in the background
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Job {
+    #[schema(value_type = String)]
+    pub id: Uuid,
+    pub status: JobStatus,
+    /// Every stage in the order they run
+    pub stages: Vec<StageState>,
+    /// The proof of task once the job succeeded
+    #[schema(value_type = Object)]
+    pub result: Option<Value>,
+    /// Why the job failed, prefixed with the failing stage
+    pub error: Option<String>,
+    /// Hash of the presentation and schema the job registers, see [`presentation_hash`]
+    #[serde(default)]
+    pub presentation_hash: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = String, format = DateTime)]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Job {
+    fn new(id: Uuid) -> Self {
+        let now = Utc::now();
+        Job {
+            id,
+            status: JobStatus::Queued,
+            stages: StageName::ALL.into_iter().map(StageState::new).collect(),
+            result: None,
+            error: None,
+            presentation_hash: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Returns the state of a stage.
+    pub fn stage(&self, stage: StageName) -> &StageState {
+        self.stages
+            .iter()
+            .find(|state| state.stage == stage)
+            .expect("jobs hold every stage")
+    }
+
+    fn stage_mut(&mut self, stage: StageName) -> &mut StageState {
+        self.stages
+            .iter_mut()
+            .find(|state| state.stage == stage)
+            .expect("jobs hold every stage")
+    }
+
+    /// Marks a stage as running, counting one more try.
+    pub fn start(&mut self, stage: StageName) {
+        self.status = JobStatus::Running;
+        let state = self.stage_mut(stage);
+        state.status = StageStatus::Running;
+        state.attempts += 1;
+        state.started_at = Some(Utc::now());
+    }
+
+    /// Keeps why a try of a running stage failed, the stage is about to be retried.
+    pub fn record_error(&mut self, stage: StageName, error: &MainProcessError) {
+        self.stage_mut(stage).error = Some(error.to_string());
+    }
+
+    /// Marks a finished stage as undone.
+    pub fn compensated(&mut self, stage: StageName) {
+        self.stage_mut(stage).status = StageStatus::Compensated;
+    }
+
+    /// Marks a stage as done, keeping what it produced.
+    pub fn complete(&mut self, stage: StageName, output: Value) {
+        let state = self.stage_mut(stage);
+        state.status = StageStatus::Done;
+        state.output = Some(output);
+        state.finished_at = Some(Utc::now());
+    }
+
+    /// Marks the job as failed, along with the stage that was running.
+    pub fn fail(&mut self, error: &MainProcessError) {
+        self.stop(error);
+        self.status = JobStatus::Failed;
+    }
+
+    /// Marks the job as stalled, along with the stage that was running. An IP registered on
+    /// chain is never given up, its remaining stages run again once the node restarts.
+    pub fn stall(&mut self, error: &MainProcessError) {
+        self.stop(error);
+        self.status = JobStatus::Stalled;
+    }
+
+    /// Checks whether the IP was registered on chain.
+    pub fn is_registered(&self) -> bool {
+        self.stage(StageName::RegisterOnchain).status == StageStatus::Done
+    }
+
+    // fails the running stage and keeps why on the job
+    fn stop(&mut self, error: &MainProcessError) {
+        let running = self
+            .stages
+            .iter_mut()
+            .find(|state| state.status == StageStatus::Running);
+        self.error = Some(match running {
+            Some(state) => {
+                state.status = StageStatus::Failed;
+                state.error = Some(error.to_string());
+                state.finished_at = Some(Utc::now());
+                format!("{}: {}", state.stage, error)
+            }
+            None => error.to_string(),
+        });
+    }
+
+    /// Marks the job as succeeded with its final result.
+    pub fn succeed(&mut self, result: Value) {
+        self.status = JobStatus::Succeeded;
+        self.result = Some(result);
+    }
+
+    /// Checks whether the job failed before it could register anything, so its presentation
+    /// may be submitted again.
+    pub fn registered_nothing(&self) -> bool {
+        self.status == JobStatus::Failed
+            && self.stage(StageName::RegisterOnchain).status == StageStatus::Pending
+    }
+}
+
+/// Hashes a presentation along with the exact schema it is registered with.
+///
+/// # Arguments
+///
+/// * `transcript_proof` - The presentation in any supported format.
+/// * `schema_id` - The schema reference as `id@version`.
+pub fn presentation_hash(
+    transcript_proof: &str,
+    schema_id: &str,
+) -> Result<FixedBytes<32>, VerifierError> {
+    let mut preimage = canonical_presentation_hash(transcript_proof)?.to_vec();
+    preimage.extend_from_slice(schema_id.as_bytes());
+    Ok(keccak256(preimage))
+}
+
+/// Hashes a presentation alone, whatever schema it is registered with.
+///
+/// The presentation is re-encoded as bincode first, so the same proof hashes the same whether it
+/// was sent as JSON, bincode or base64. Validators key the IP a proof backs on this, registering
+/// it again under another schema or version doesn't make it a different proof.
+///
+/// # Arguments
+///
+/// * `transcript_proof` - The presentation in any supported format.
+pub fn canonical_presentation_hash(
+    transcript_proof: &str,
+) -> Result<FixedBytes<32>, VerifierError> {
+    let presentation = decode_presentation(transcript_proof.as_bytes())?;
+    Ok(keccak256(encode_presentation(&presentation)?))
+}
+
+/// Outcome of [`JobStore::create`]
+#[derive(Debug)]
+pub enum Submission {
+    /// A new job was queued
+    Created(Job),
+    /// The presentation or idempotency key already has a job, nothing was queued
+    Existing(Job),
+}
+
+/// Jobs persisted on disk, along with the request each one runs
+pub struct JobStore {
+    db: sled::Db,
+    jobs: sled::Tree,
+    requests: sled::Tree,
+    // presentation hash to the job registering it
+    presentations: sled::Tree,
+    // client idempotency key to the job it created
+    idempotency_keys: sled::Tree,
+}
+
+impl JobStore {
+    /// Opens the store in a directory, creating it if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory the store lives in.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MainProcessError> {
+        Self::with_config(sled::Config::new().path(path))
+    }
+
+    /// Opens a store that is deleted once dropped.
+    #[cfg(test)]
+    pub(crate) fn temporary() -> Result<Self, MainProcessError> {
+        Self::with_config(sled::Config::new().temporary(true))
+    }
+
+    fn with_config(config: sled::Config) -> Result<Self, MainProcessError> {
+        let db = config.open().map_err(store_error)?;
+        let jobs = db.open_tree("jobs").map_err(store_error)?;
+        let requests = db.open_tree("requests").map_err(store_error)?;
+        let presentations = db.open_tree("presentations").map_err(store_error)?;
+        let idempotency_keys = db.open_tree("idempotency_keys").map_err(store_error)?;
+        Ok(JobStore {
+            db,
+            jobs,
+            requests,
+            presentations,
+            idempotency_keys,
+        })
+    }
+
+    /// Creates a queued job for a request, unless its presentation or idempotency key already
+    /// has one. That job is returned instead, a presentation whose job
+    /// [registered nothing](Job::registered_nothing) excepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - Everything the job needs to run, kept until the job is gone.
+    /// * `presentation` - The [`presentation_hash`] of the request.
+    /// * `idempotency_key` - Key the client sent so retrying the request is safe.
+    pub fn create(
+        &self,
+        request: &impl Serialize,
+        presentation: FixedBytes<32>,
+        idempotency_key: Option<&str>,
+    ) -> Result<Submission, MainProcessError> {
+        let mut job = Job::new(Uuid::new_v4());
+        job.presentation_hash = Some(presentation.to_string());
+        let request = encode(request)?;
+        let encoded = encode(&job)?;
+
+        let existing = (
+            &self.jobs,
+            &self.requests,
+            &self.presentations,
+            &self.idempotency_keys,
+        )
+            .transaction(|(jobs, requests, presentations, keys)| {
+                let read = |id: &[u8]| match jobs.get(id)? {
+                    Some(bytes) => decode_job(&bytes).map_err(ConflictableTransactionError::Abort),
+                    None => Err(ConflictableTransactionError::Abort(UnknownJob(
+                        String::from_utf8_lossy(id).to_string(),
+                    ))),
+                };
+
+                // a key always stands for the job it created, even one that failed
+                if let Some(key) = idempotency_key {
+                    if let Some(id) = keys.get(key.as_bytes())? {
+                        let existing = read(&id)?;
+                        if existing.presentation_hash != job.presentation_hash {
+                            return Err(ConflictableTransactionError::Abort(Conflict(
+                                "The Idempotency-Key was already used for another registration"
+                                    .to_string(),
+                            )));
+                        }
+                        return Ok(Some(existing));
+                    }
+                }
+                if let Some(id) = presentations.get(presentation.as_slice())? {
+                    let existing = read(&id)?;
+                    if !existing.registered_nothing() {
+                        return Ok(Some(existing));
+                    }
+                }
+
+                let id = job.id.as_bytes().as_slice();
+                jobs.insert(id, encoded.as_slice())?;
+                requests.insert(id, request.as_slice())?;
+                presentations.insert(presentation.as_slice(), id)?;
+                if let Some(key) = idempotency_key {
+                    keys.insert(key.as_bytes(), id)?;
+                }
+                Ok(None)
+            })
+            .map_err(|e| match e {
+                TransactionError::Abort(e) => e,
+                TransactionError::Storage(e) => store_error(e),
+            })?;
+
+        match existing {
+            Some(existing) => Ok(Submission::Existing(existing)),
+            None => {
+                self.db.flush().map_err(store_error)?;
+                Ok(Submission::Created(job))
+            }
+        }
+    }
+
+    /// Looks up a job by ID.
+    pub fn get(&self, id: Uuid) -> Result<Job, MainProcessError> {
+        match self.jobs.get(id.as_bytes()).map_err(store_error)? {
+            Some(bytes) => decode_job(&bytes),
+            None => Err(UnknownJob(id.to_string())),
+        }
+    }
+
+    /// Returns the request a job was created for.
+    pub fn request<T: DeserializeOwned>(&self, id: Uuid) -> Result<T, MainProcessError> {
+        match self.requests.get(id.as_bytes()).map_err(store_error)? {
+            Some(bytes) => decode(&bytes),
+            None => Err(UnknownJob(id.to_string())),
+        }
+    }
+
+    /// Lists the jobs that were queued, running or stalled, oldest first.
+    pub fn unfinished(&self) -> Result<Vec<Job>, MainProcessError> {
+        let mut jobs = Vec::new();
+        for entry in self.jobs.iter() {
+            let (_, bytes) = entry.map_err(store_error)?;
+            let job = decode_job(&bytes)?;
+            if matches!(
+                job.status,
+                JobStatus::Queued | JobStatus::Running | JobStatus::Stalled
+            ) {
+                jobs.push(job);
+            }
+        }
+        jobs.sort_by_key(|job| job.created_at);
+        Ok(jobs)
+    }
+
+    /// Persists a job, it is on disk once this returns.
+    pub fn save(&self, job: &mut Job) -> Result<(), MainProcessError> {
+        job.updated_at = Utc::now();
+        self.jobs
+            .insert(job.id.as_bytes(), encode(job)?)
+            .map_err(store_error)?;
+        self.db.flush().map_err(store_error)?;
+        Ok(())
+    }
+}
+
+fn encode(value: &impl Serialize) -> Result<Vec<u8>, MainProcessError> {
+    serde_json::to_vec(value).map_err(|e| MainProcessError::Unexpected(Report::new(e)))
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, MainProcessError> {
+    serde_json::from_slice(bytes).map_err(|e| MainProcessError::Unexpected(Report::new(e)))
+}
+
+// jobs saved before a stage was added get it as pending
+fn decode_job(bytes: &[u8]) -> Result<Job, MainProcessError> {
+    let mut job: Job = decode(bytes)?;
+    for (index, stage) in StageName::ALL.into_iter().enumerate() {
+        if !job.stages.iter().any(|state| state.stage == stage) {
+            job.stages
+                .insert(index.min(job.stages.len()), StageState::new(stage));
+        }
+    }
+    Ok(job)
+}
+
+fn store_error(error: sled::Error) -> MainProcessError {
+    MainProcessError::Unexpected(Report::new(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> JobStore {
+        JobStore::temporary().unwrap()
+    }
+
+    fn hash(seed: &str) -> FixedBytes<32> {
+        keccak256(seed)
+    }
+
+    // a job for a presentation no other job registers
+    fn create(store: &JobStore, request: &impl Serialize) -> Job {
+        match store
+            .create(request, hash(&Uuid::new_v4().to_string()), None)
+            .unwrap()
+        {
+            Submission::Created(job) => job,
+            Submission::Existing(job) => panic!("job {} already exists", job.id),
+        }
+    }
+
+    #[test]
+    fn test_job_progress_is_persisted() {
+        let store = store();
+        let mut job = create(&store, &serde_json::json!({"schema_id": "x-post@1"}));
+        assert_eq!(job.status, JobStatus::Queued);
+        assert_eq!(job.stages.len(), StageName::ALL.len());
+
+        job.start(StageName::Verify);
+        job.complete(StageName::Verify, serde_json::json!({"notary": "test"}));
+        job.start(StageName::UploadMedia);
+        store.save(&mut job).unwrap();
+
+        let stored = store.get(job.id).unwrap();
+        assert_eq!(stored.status, JobStatus::Running);
+        assert_eq!(stored.stage(StageName::Verify).status, StageStatus::Done);
+        assert_eq!(
+            stored.stage(StageName::Verify).output,
+            Some(serde_json::json!({"notary": "test"}))
+        );
+        assert_eq!(
+            stored.stage(StageName::UploadMedia).status,
+            StageStatus::Running
+        );
+        let request: Value = store.request(job.id).unwrap();
+        assert_eq!(request["schema_id"], "x-post@1");
+
+        assert!(matches!(store.get(Uuid::new_v4()), Err(UnknownJob(_))));
+    }
+
+    #[test]
+    fn test_unfinished_jobs_are_listed() {
+        let store = store();
+        let queued = create(&store, &Value::Null);
+        let mut running = create(&store, &Value::Null);
+        running.start(StageName::Verify);
+        store.save(&mut running).unwrap();
+        let mut done = create(&store, &Value::Null);
+        done.succeed(Value::Null);
+        store.save(&mut done).unwrap();
+        let mut failed = create(&store, &Value::Null);
+        failed.fail(&UnknownJob("gone".into()));
+        store.save(&mut failed).unwrap();
+        let mut stalled = create(&store, &Value::Null);
+        stalled.start(StageName::RegisterOnchain);
+        stalled.complete(StageName::RegisterOnchain, Value::Null);
+        stalled.start(StageName::PublishDa);
+        stalled.stall(&MainProcessError::Timeout("disperser".into()));
+        store.save(&mut stalled).unwrap();
+
+        let ids: Vec<Uuid> = store
+            .unfinished()
+            .unwrap()
+            .iter()
+            .map(|job| job.id)
+            .collect();
+        assert_eq!(ids, vec![queued.id, running.id, stalled.id]);
+        assert!(stalled.is_registered());
+        assert_eq!(
+            stalled.stage(StageName::PublishDa).status,
+            StageStatus::Failed
+        );
+
+        assert!(StageName::UploadMedia.is_repeatable());
+        assert!(!StageName::RegisterOnchain.is_repeatable());
+    }
+
+    #[test]
+    fn test_failure_names_the_running_stage() {
+        let mut job = Job::new(Uuid::new_v4());
+        job.start(StageName::Verify);
+        job.complete(StageName::Verify, Value::Null);
+        job.start(StageName::UploadMedia);
+        job.fail(&MainProcessError::BadFileUse("gateway timeout".into()));
+
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(
+            job.stage(StageName::UploadMedia).status,
+            StageStatus::Failed
+        );
+        assert_eq!(
+            job.error.as_deref(),
+            Some("upload-media: Failed to upload file: gateway timeout")
+        );
+        assert_eq!(job.stage(StageName::Verify).status, StageStatus::Done);
+    }
+
+    #[test]
+    fn test_duplicate_submissions_return_the_existing_job() {
+        let store = store();
+        let Submission::Created(mut first) = store.create(&Value::Null, hash("a"), None).unwrap()
+        else {
+            panic!("the first submission creates a job");
+        };
+        let Submission::Existing(again) = store.create(&Value::Null, hash("a"), None).unwrap()
+        else {
+            panic!("the same presentation is not registered twice");
+        };
+        assert_eq!(again.id, first.id);
+
+        // a presentation whose job failed early may be submitted again
+        first.start(StageName::UploadMedia);
+        first.fail(&MainProcessError::BadFileUse("gateway timeout".into()));
+        store.save(&mut first).unwrap();
+        let Submission::Created(second) = store.create(&Value::Null, hash("a"), None).unwrap()
+        else {
+            panic!("a job that registered nothing doesn't block its presentation");
+        };
+
+        // once on chain, it never is
+        let mut second = second;
+        second.start(StageName::RegisterOnchain);
+        second.fail(&MainProcessError::Registration("reverted".into()));
+        store.save(&mut second).unwrap();
+        assert!(matches!(
+            store.create(&Value::Null, hash("a"), None).unwrap(),
+            Submission::Existing(job) if job.id == second.id
+        ));
+    }
+
+    #[test]
+    fn test_idempotency_keys_return_the_job_they_created() {
+        let store = store();
+        let Submission::Created(job) = store
+            .create(&Value::Null, hash("a"), Some("retry-1"))
+            .unwrap()
+        else {
+            panic!("the first submission creates a job");
+        };
+        assert!(matches!(
+            store.create(&Value::Null, hash("a"), Some("retry-1")).unwrap(),
+            Submission::Existing(existing) if existing.id == job.id
+        ));
+        assert!(matches!(
+            store.create(&Value::Null, hash("b"), Some("retry-1")),
+            Err(Conflict(_))
+        ));
+        assert!(matches!(
+            store
+                .create(&Value::Null, hash("b"), Some("retry-2"))
+                .unwrap(),
+            Submission::Created(_)
+        ));
+    }
+}
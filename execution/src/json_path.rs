@@ -0,0 +1,404 @@
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde_json::value::RawValue;
+use std::fmt;
+
+use crate::error::MainProcessError::{self, BadContentSchema};
+
+/// A single step of a [`JsonPath`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// An object member, or an array index when the name is numeric
+    Field(String),
+    /// An array element
+    Index(usize),
+    /// Every member of an object or element of an array
+    Wildcard,
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Field(name) => write!(f, "{}", name),
+            Segment::Index(index) => write!(f, "[{}]", index),
+            Segment::Wildcard => write!(f, "*"),
+        }
+    }
+}
+
+/// A path into a JSON document
+///
+/// Two syntaxes are accepted:
+/// - dotted paths such as `data.user.legacy.screen_name`, `includes.media[0].url`,
+///   `items[*].id` or `$["key.with.dots"]`
+/// - JSON pointers such as `/data/user/name`, where `*` matches every member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    /// Creates a path selecting a single top-level member.
+    pub fn field(name: &str) -> Self {
+        JsonPath {
+            segments: vec![Segment::Field(name.to_string())],
+        }
+    }
+
+    /// Parses a dotted path or a JSON pointer.
+    pub fn parse(path: &str) -> Result<Self, MainProcessError> {
+        let path = path.trim();
+        let segments = if path.starts_with('/') {
+            parse_pointer(path)
+        } else {
+            parse_dotted(path)?
+        };
+
+        if segments.is_empty() {
+            return Err(BadContentSchema(format!(
+                "JSON path '{}' selects nothing",
+                path
+            )));
+        }
+
+        Ok(JsonPath { segments })
+    }
+
+    /// Checks whether the path can select more than one value.
+    pub fn has_wildcard(&self) -> bool {
+        self.segments.contains(&Segment::Wildcard)
+    }
+
+    /// Selects the values this path points at, borrowed from the document so their position
+    /// in the transcript can be recovered.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The parsed document.
+    pub fn select<'a>(&self, root: &'a RawValue) -> Result<Vec<&'a RawValue>, MainProcessError> {
+        let mut current = vec![root];
+
+        for (position, segment) in self.segments.iter().enumerate() {
+            // after a wildcard, values missing from some elements are skipped rather than fatal
+            let strict = !self.segments[..position].contains(&Segment::Wildcard);
+            let mut next = Vec::new();
+
+            for value in current {
+                match (segment, Node::of(value)) {
+                    (Segment::Wildcard, Node::Object) => {
+                        next.extend(object_entries(value)?.into_iter().map(|(_, v)| v))
+                    }
+                    (Segment::Wildcard, Node::Array) => next.extend(array_items(value)?),
+                    (Segment::Field(name), Node::Object) => {
+                        match object_entries(value)?.into_iter().find(|(k, _)| k == name) {
+                            Some((_, member)) => next.push(member),
+                            None if strict => {
+                                return Err(self.failure(position, "field not found".into()))
+                            }
+                            None => {}
+                        }
+                    }
+                    (Segment::Field(name), Node::Array) if name.parse::<usize>().is_ok() => {
+                        let index = name.parse::<usize>().unwrap();
+                        self.push_item(&mut next, value, index, position, strict)?
+                    }
+                    (Segment::Index(index), Node::Array) => {
+                        self.push_item(&mut next, value, *index, position, strict)?
+                    }
+                    (_, node) if strict => {
+                        return Err(
+                            self.failure(position, format!("cannot step into {}", node.describe()))
+                        )
+                    }
+                    _ => {}
+                }
+            }
+
+            current = next;
+        }
+
+        if current.is_empty() {
+            return Err(BadContentSchema(format!(
+                "JSON path '{}' matched no values",
+                self
+            )));
+        }
+
+        Ok(current)
+    }
+
+    fn push_item<'a>(
+        &self,
+        next: &mut Vec<&'a RawValue>,
+        array: &'a RawValue,
+        index: usize,
+        position: usize,
+        strict: bool,
+    ) -> Result<(), MainProcessError> {
+        let items = array_items(array)?;
+        let count = items.len();
+        match items.into_iter().nth(index) {
+            Some(item) => next.push(item),
+            None if strict => {
+                return Err(self.failure(
+                    position,
+                    format!("index out of range, array has {} items", count),
+                ))
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn failure(&self, position: usize, reason: String) -> MainProcessError {
+        BadContentSchema(format!(
+            "JSON path '{}' failed at segment '{}': {}",
+            self, self.segments[position], reason
+        ))
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (position, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Index(_) => write!(f, "{}", segment)?,
+                _ if position == 0 => write!(f, "{}", segment)?,
+                _ => write!(f, ".{}", segment)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_pointer(path: &str) -> Vec<Segment> {
+    path[1..]
+        .split('/')
+        .map(|token| match token {
+            "*" => Segment::Wildcard,
+            _ => Segment::Field(token.replace("~1", "/").replace("~0", "~")),
+        })
+        .collect()
+}
+
+fn parse_dotted(path: &str) -> Result<Vec<Segment>, MainProcessError> {
+    let invalid =
+        |reason: &str| BadContentSchema(format!("Invalid JSON path '{}': {}", path, reason));
+
+    let rest = path.strip_prefix('$').unwrap_or(path);
+    let rest = rest.strip_prefix('.').unwrap_or(rest);
+    let mut chars = rest.chars().peekable();
+    let mut segments = Vec::new();
+    // a dot must be followed by a field name
+    let mut expect_field = !rest.is_empty() && !rest.starts_with('[');
+
+    while chars.peek().is_some() || expect_field {
+        match chars.peek() {
+            Some('[') => {
+                chars.next();
+                let mut inner = String::new();
+                let mut closed = false;
+                let quote = chars.next_if(|c| *c == '"' || *c == '\'');
+                while let Some(c) = chars.next() {
+                    match quote {
+                        Some(q) if c == q && chars.peek() == Some(&']') => {
+                            chars.next();
+                            closed = true;
+                            break;
+                        }
+                        None if c == ']' => {
+                            closed = true;
+                            break;
+                        }
+                        _ => inner.push(c),
+                    }
+                }
+                if !closed {
+                    return Err(invalid("unclosed '['"));
+                }
+
+                segments.push(match (quote, inner.trim()) {
+                    (Some(_), _) => Segment::Field(inner),
+                    (None, "*") => Segment::Wildcard,
+                    (None, index) => {
+                        Segment::Index(index.parse().map_err(|_| {
+                            invalid(&format!("'[{}]' is not an array index", index))
+                        })?)
+                    }
+                });
+                expect_field = false;
+            }
+            Some('.') if !expect_field => {
+                chars.next();
+                expect_field = true;
+            }
+            _ => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| *c != '.' && *c != '[') {
+                    name.push(c);
+                }
+                if name.is_empty() {
+                    return Err(invalid("empty field name"));
+                }
+                segments.push(match name.as_str() {
+                    "*" => Segment::Wildcard,
+                    _ => Segment::Field(name),
+                });
+                expect_field = false;
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+/// What kind of JSON value a raw value holds
+enum Node {
+    Object,
+    Array,
+    Scalar(&'static str),
+}
+
+impl Node {
+    fn of(value: &RawValue) -> Self {
+        match value.get().trim_start().as_bytes().first() {
+            Some(b'{') => Node::Object,
+            Some(b'[') => Node::Array,
+            Some(b'"') => Node::Scalar("a string"),
+            Some(b't') | Some(b'f') => Node::Scalar("a boolean"),
+            Some(b'n') => Node::Scalar("null"),
+            _ => Node::Scalar("a number"),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Node::Object => "an object",
+            Node::Array => "an array",
+            Node::Scalar(kind) => kind,
+        }
+    }
+}
+
+fn parse_error(e: serde_json::Error) -> MainProcessError {
+    BadContentSchema(format!("Failed to parse JSON body: {}", e))
+}
+
+fn array_items(value: &RawValue) -> Result<Vec<&RawValue>, MainProcessError> {
+    serde_json::from_str(value.get()).map_err(parse_error)
+}
+
+// object members in document order, so wildcard results are stable
+fn object_entries(value: &RawValue) -> Result<Vec<(String, &RawValue)>, MainProcessError> {
+    serde_json::from_str::<Entries>(value.get())
+        .map(|entries| entries.0)
+        .map_err(parse_error)
+}
+
+struct Entries<'a>(Vec<(String, &'a RawValue)>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for Entries<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = Entries<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Entries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(document: &str, path: &str) -> Result<Vec<String>, MainProcessError> {
+        let root: &RawValue = serde_json::from_str(document).unwrap();
+        let values = JsonPath::parse(path)?.select(root)?;
+        Ok(values.iter().map(|v| v.get().to_string()).collect())
+    }
+
+    #[test]
+    fn test_parse_syntaxes() {
+        let path = JsonPath::parse("$.includes.media[0]['url.full']").unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                Segment::Field("includes".into()),
+                Segment::Field("media".into()),
+                Segment::Index(0),
+                Segment::Field("url.full".into()),
+            ]
+        );
+
+        let pointer = JsonPath::parse("/data/a~1b/*/0").unwrap();
+        assert_eq!(
+            pointer.segments,
+            vec![
+                Segment::Field("data".into()),
+                Segment::Field("a/b".into()),
+                Segment::Wildcard,
+                Segment::Field("0".into()),
+            ]
+        );
+
+        assert!(JsonPath::parse("a..b").is_err());
+        assert!(JsonPath::parse("a.").is_err());
+        assert!(JsonPath::parse("a[1").is_err());
+        assert!(JsonPath::parse("a[x]").is_err());
+        assert!(JsonPath::parse("").is_err());
+    }
+
+    #[test]
+    fn test_select_nested_values() {
+        let document = r#"{"data":{"user":{"legacy":{"screen_name":"forge"}}},"includes":{"media":[{"url":"a"},{"url":"b"},{"type":"gif"}]}}"#;
+
+        assert_eq!(
+            select(document, "data.user.legacy.screen_name").unwrap(),
+            vec![r#""forge""#]
+        );
+        assert_eq!(
+            select(document, "includes.media[1].url").unwrap(),
+            vec![r#""b""#]
+        );
+        assert_eq!(
+            select(document, "/includes/media/0/url").unwrap(),
+            vec![r#""a""#]
+        );
+        // elements without the field are skipped under a wildcard
+        assert_eq!(
+            select(document, "includes.media[*].url").unwrap(),
+            vec![r#""a""#, r#""b""#]
+        );
+    }
+
+    #[test]
+    fn test_errors_name_the_failing_segment() {
+        let document = r#"{"data":{"user":{"name":"forge"},"items":[1,2]}}"#;
+
+        let error = select(document, "data.user.legacy.screen_name").unwrap_err();
+        assert!(error.to_string().contains("segment 'legacy'"));
+
+        let error = select(document, "data.items[5]").unwrap_err();
+        assert!(error.to_string().contains("segment '[5]'"));
+        assert!(error.to_string().contains("2 items"));
+
+        let error = select(document, "data.user.name.first").unwrap_err();
+        assert!(error.to_string().contains("segment 'first'"));
+        assert!(error.to_string().contains("a string"));
+
+        assert!(select(document, "data.items[*].id").is_err());
+    }
+}
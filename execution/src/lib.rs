@@ -1,2 +1,11 @@
+pub mod assertion;
+pub mod config;
 pub mod error;
+pub mod jobs;
+pub mod json_path;
+pub mod pipeline;
+pub mod schema_check;
+pub mod schema_registry;
+pub mod template;
+pub mod transform;
 pub mod utils;
@@ -1,12 +1,13 @@
-mod error;
 mod server;
 mod service;
-mod utils;
+mod stages;
 
 use server::run_server;
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
-    let _ = run_server().await;
+    if let Err(e) = run_server().await {
+        eprintln!("Forge server stopped: {}", e);
+    }
 }
@@ -0,0 +1,375 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{future::Future, pin::Pin, time::Duration};
+
+use crate::{
+    error::MainProcessError::{self, Timeout},
+    jobs::{Job, JobStore, StageName, StageStatus},
+};
+
+/// How often and how long a stage may run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StagePolicy {
+    /// Tries before the stage fails for good, only transient errors are retried
+    pub attempts: u32,
+    /// Wait before the first retry, doubled for each one after it
+    pub backoff: Duration,
+    /// Longest wait between two tries
+    pub max_backoff: Duration,
+    /// Longest a single try may take, `None` for stages whose outcome is unknown when cut short
+    pub timeout: Option<Duration>,
+}
+
+impl StagePolicy {
+    /// Runs the stage once without a time limit.
+    pub const ONCE: StagePolicy = StagePolicy {
+        attempts: 1,
+        backoff: Duration::ZERO,
+        max_backoff: Duration::ZERO,
+        timeout: None,
+    };
+
+    /// Retries transient failures with exponential backoff, starting at two seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `attempts` - Tries before the stage fails for good.
+    /// * `timeout` - Longest a single try may take.
+    pub fn retrying(attempts: u32, timeout: Duration) -> Self {
+        StagePolicy {
+            attempts,
+            backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(30),
+            timeout: Some(timeout),
+        }
+    }
+
+    // wait before the given retry, counted from 1
+    fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
+
+/// A step of a registration with typed input and output
+pub trait Stage: Sync {
+    type Input: Send + Sync;
+    /// Journaled once the stage is done, a resumed job reuses it instead of running the stage
+    type Output: Serialize + DeserializeOwned + Clone + Send + Sync;
+
+    const NAME: StageName;
+
+    /// Once this stage is done, stages before it are no longer compensated
+    const IRREVERSIBLE: bool = false;
+
+    /// Runs again when a job resumes instead of reusing the journal, for stages that rebuild
+    /// state the journal doesn't hold
+    const REPLAYED: bool = false;
+
+    fn policy(&self) -> StagePolicy;
+
+    /// Runs the stage once, the pipeline takes care of retries and timeouts.
+    fn run(
+        &self,
+        input: &Self::Input,
+    ) -> impl Future<Output = Result<Self::Output, MainProcessError>> + Send;
+
+    /// Undoes the stage's side effects when a later stage failed for good.
+    fn compensate(
+        &self,
+        _output: Self::Output,
+    ) -> impl Future<Output = Result<(), MainProcessError>> + Send + 'static {
+        async { Ok(()) }
+    }
+}
+
+type Compensation = Pin<Box<dyn Future<Output = Result<(), MainProcessError>> + Send>>;
+
+/// Runs the stages of a job, journaling what each produced and undoing them when one fails
+pub struct Pipeline<'a> {
+    jobs: &'a JobStore,
+    job: &'a mut Job,
+    // undo steps of the stages since the last irreversible one, latest last
+    compensations: Vec<(StageName, Compensation)>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(jobs: &'a JobStore, job: &'a mut Job) -> Self {
+        Pipeline {
+            jobs,
+            job,
+            compensations: Vec::new(),
+        }
+    }
+
+    /// Returns the journal entry of a stage that finished.
+    pub fn recorded<T: DeserializeOwned>(
+        &self,
+        stage: StageName,
+    ) -> Result<Option<T>, MainProcessError> {
+        let state = self.job.stage(stage);
+        match (&state.status, &state.output) {
+            (StageStatus::Done, Some(output)) => serde_json::from_value(output.clone())
+                .map(Some)
+                .map_err(|e| eyre::eyre!("Unreadable journal entry for {}: {}", stage, e).into()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Runs a stage unless the journal already holds its output. When it fails for good,
+    /// the stages before it are compensated, latest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `stage` - The stage to run.
+    /// * `input` - What the stages before it produced.
+    pub async fn run<S: Stage>(
+        &mut self,
+        stage: &S,
+        input: S::Input,
+    ) -> Result<S::Output, MainProcessError> {
+        let output = match self.recorded::<S::Output>(S::NAME)? {
+            Some(output) if !S::REPLAYED => output,
+            _ => match self.attempt(stage, &input).await {
+                Ok(output) => output,
+                Err(e) => {
+                    self.unwind().await;
+                    return Err(e);
+                }
+            },
+        };
+
+        if S::IRREVERSIBLE {
+            self.compensations.clear();
+        } else {
+            let compensation = stage.compensate(output.clone());
+            self.compensations.push((S::NAME, Box::pin(compensation)));
+        }
+        Ok(output)
+    }
+
+    // tries the stage as often as its policy allows, the output is saved the moment it exists
+    async fn attempt<S: Stage>(
+        &mut self,
+        stage: &S,
+        input: &S::Input,
+    ) -> Result<S::Output, MainProcessError> {
+        let policy = stage.policy();
+        let mut attempt = 1;
+        loop {
+            self.job.start(S::NAME);
+            self.jobs.save(self.job)?;
+
+            let result = match policy.timeout {
+                Some(limit) => tokio::time::timeout(limit, stage.run(input))
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(Timeout(format!("{} took longer than {:?}", S::NAME, limit)))
+                    }),
+                None => stage.run(input).await,
+            };
+
+            match result {
+                Ok(output) => {
+                    let journaled = serde_json::to_value(&output)
+                        .map_err(|e| eyre::eyre!("Failed to journal {}: {}", S::NAME, e))?;
+                    self.job.complete(S::NAME, journaled);
+                    self.jobs.save(self.job)?;
+                    return Ok(output);
+                }
+                Err(e) if attempt < policy.attempts && e.is_transient() => {
+                    let delay = policy.delay(attempt);
+                    println!(
+                        "Job {}: {} failed, retrying in {:?}: {}",
+                        self.job.id,
+                        S::NAME,
+                        delay,
+                        e
+                    );
+                    self.job.record_error(S::NAME, &e);
+                    self.jobs.save(self.job)?;
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // a compensation that fails is reported and doesn't stop the ones before it
+    async fn unwind(&mut self) {
+        while let Some((stage, compensation)) = self.compensations.pop() {
+            match compensation.await {
+                Ok(()) => self.job.compensated(stage),
+                Err(e) => println!("Job {}: failed to undo {}: {}", self.job.id, stage, e),
+            }
+        }
+        if let Err(e) = self.jobs.save(self.job) {
+            eprintln!("Failed to save job {}: {}", self.job.id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::{JobStatus, Submission};
+    use alloy::primitives::FixedBytes;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    };
+
+    // a stage failing transiently a number of times before it succeeds
+    struct Flaky {
+        name: StageName,
+        failures: u32,
+        runs: AtomicU32,
+        undone: Arc<Mutex<Vec<StageName>>>,
+    }
+
+    impl Flaky {
+        fn new(name: StageName, failures: u32, undone: &Arc<Mutex<Vec<StageName>>>) -> Self {
+            Flaky {
+                name,
+                failures,
+                runs: AtomicU32::new(0),
+                undone: undone.clone(),
+            }
+        }
+    }
+
+    macro_rules! flaky_stage {
+        ($stage:ident, $name:expr, $irreversible:expr) => {
+            struct $stage(Flaky);
+
+            impl Stage for $stage {
+                type Input = u32;
+                type Output = u32;
+                const NAME: StageName = $name;
+                const IRREVERSIBLE: bool = $irreversible;
+
+                fn policy(&self) -> StagePolicy {
+                    StagePolicy {
+                        attempts: 3,
+                        backoff: Duration::from_millis(1),
+                        max_backoff: Duration::from_millis(1),
+                        timeout: Some(Duration::from_secs(1)),
+                    }
+                }
+
+                async fn run(&self, input: &u32) -> Result<u32, MainProcessError> {
+                    let run = self.0.runs.fetch_add(1, Ordering::SeqCst) + 1;
+                    if run <= self.0.failures {
+                        Err(MainProcessError::BadFileUse(format!("try {}", run)))
+                    } else {
+                        Ok(input + 1)
+                    }
+                }
+
+                fn compensate(
+                    &self,
+                    _output: u32,
+                ) -> impl Future<Output = Result<(), MainProcessError>> + Send + 'static {
+                    let undone = self.0.undone.clone();
+                    let name = self.0.name;
+                    async move {
+                        undone.lock().unwrap().push(name);
+                        Ok(())
+                    }
+                }
+            }
+        };
+    }
+
+    flaky_stage!(Media, StageName::UploadMedia, false);
+    flaky_stage!(Metadata, StageName::UploadMetadata, false);
+    flaky_stage!(Onchain, StageName::RegisterOnchain, true);
+    flaky_stage!(Publish, StageName::PublishDa, false);
+
+    fn new_job() -> (JobStore, Job) {
+        let store = JobStore::temporary().unwrap();
+        // every test gets its own store, the presentation is never seen twice
+        match store.create(&(), FixedBytes::ZERO, None).unwrap() {
+            Submission::Created(job) => (store, job),
+            Submission::Existing(_) => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let (store, mut job) = new_job();
+        let undone = Arc::new(Mutex::new(Vec::new()));
+        let media = Media(Flaky::new(StageName::UploadMedia, 2, &undone));
+
+        let mut pipeline = Pipeline::new(&store, &mut job);
+        assert_eq!(pipeline.run(&media, 1).await.unwrap(), 2);
+        assert_eq!(media.0.runs.load(Ordering::SeqCst), 3);
+
+        let state = store
+            .get(job.id)
+            .unwrap()
+            .stage(StageName::UploadMedia)
+            .clone();
+        assert_eq!(state.status, StageStatus::Done);
+        assert_eq!(state.attempts, 3);
+        assert_eq!(state.output, Some(2.into()));
+    }
+
+    #[tokio::test]
+    async fn test_failure_compensates_back_to_the_irreversible_stage() {
+        let (store, mut job) = new_job();
+        let undone = Arc::new(Mutex::new(Vec::new()));
+        let media = Media(Flaky::new(StageName::UploadMedia, 0, &undone));
+        let metadata = Metadata(Flaky::new(StageName::UploadMetadata, 0, &undone));
+        let failing = Onchain(Flaky::new(StageName::RegisterOnchain, 3, &undone));
+
+        let mut pipeline = Pipeline::new(&store, &mut job);
+        pipeline.run(&media, 1).await.unwrap();
+        pipeline.run(&metadata, 2).await.unwrap();
+        assert!(pipeline.run(&failing, 3).await.is_err());
+        assert_eq!(
+            *undone.lock().unwrap(),
+            vec![StageName::UploadMetadata, StageName::UploadMedia]
+        );
+        assert_eq!(
+            job.stage(StageName::UploadMedia).status,
+            StageStatus::Compensated
+        );
+
+        // nothing before an irreversible stage is undone
+        let (store, mut job) = new_job();
+        let undone = Arc::new(Mutex::new(Vec::new()));
+        let media = Media(Flaky::new(StageName::UploadMedia, 0, &undone));
+        let onchain = Onchain(Flaky::new(StageName::RegisterOnchain, 0, &undone));
+        let failing = Publish(Flaky::new(StageName::PublishDa, 3, &undone));
+
+        let mut pipeline = Pipeline::new(&store, &mut job);
+        pipeline.run(&media, 1).await.unwrap();
+        pipeline.run(&onchain, 2).await.unwrap();
+        assert!(pipeline.run(&failing, 3).await.is_err());
+        assert!(undone.lock().unwrap().is_empty());
+        assert_eq!(job.status, JobStatus::Running);
+    }
+
+    #[tokio::test]
+    async fn test_journaled_stages_are_not_run_again() {
+        let (store, mut job) = new_job();
+        let undone = Arc::new(Mutex::new(Vec::new()));
+        job.start(StageName::UploadMedia);
+        job.complete(StageName::UploadMedia, 41.into());
+
+        let media = Media(Flaky::new(StageName::UploadMedia, 0, &undone));
+        let mut pipeline = Pipeline::new(&store, &mut job);
+        assert_eq!(pipeline.run(&media, 1).await.unwrap(), 41);
+        assert_eq!(media.0.runs.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_limit() {
+        let policy = StagePolicy::retrying(5, Duration::from_secs(60));
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(2), Duration::from_secs(4));
+        assert_eq!(policy.delay(5), Duration::from_secs(30));
+    }
+}
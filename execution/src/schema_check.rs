@@ -0,0 +1,257 @@
+use regex::Regex;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    assertion::{Assertion, Check},
+    error::MainProcessError::{self, BadContentSchema},
+    schema_registry::SchemaDefinition,
+    template,
+    utils::{check_key, ContentSchema, Metadata},
+};
+
+/// A schema document with rules beyond what its type enforces
+pub trait SemanticCheck {
+    /// Lists every rule the document breaks, each prefixed with the path of the offending field.
+    fn violations(&self) -> Vec<String>;
+}
+
+impl SemanticCheck for ContentSchema {
+    fn violations(&self) -> Vec<String> {
+        schema_violations(&self.name, &self.metadata, &self.assertions)
+    }
+}
+
+impl SemanticCheck for SchemaDefinition {
+    fn violations(&self) -> Vec<String> {
+        schema_violations(&self.name, &self.metadata, &self.assertions)
+    }
+}
+
+/// Parses a schema document, refusing unknown fields and listing every semantic violation.
+///
+/// Type errors name the path of the field that failed to parse, e.g. `metadata.properties[0].mime`.
+///
+/// # Arguments
+///
+/// * `json` - The schema document.
+pub fn parse_strict<T: DeserializeOwned + SemanticCheck>(
+    json: &str,
+) -> Result<T, MainProcessError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    let document: T = serde_path_to_error::deserialize(deserializer)
+        .map_err(|e| BadContentSchema(format!("{}: {}", e.path(), e.inner())))?;
+
+    let violations = document.violations();
+    if violations.is_empty() {
+        Ok(document)
+    } else {
+        Err(BadContentSchema(violations.join("; ")))
+    }
+}
+
+// collects violations as `path: message`
+#[derive(Default)]
+struct Violations(Vec<String>);
+
+impl Violations {
+    fn push(&mut self, path: &str, message: impl std::fmt::Display) {
+        self.0.push(format!("{}: {}", path, message));
+    }
+
+    fn key(&mut self, path: &str, key: &str) {
+        if let Err(e) = check_key(key) {
+            self.push(path, reason(e));
+        }
+    }
+
+    fn template(&mut self, path: &str, text: &str) {
+        match template::keys(text) {
+            Ok(keys) => keys.into_iter().for_each(|key| self.key(path, key)),
+            Err(e) => self.push(path, reason(e)),
+        }
+    }
+}
+
+// the message without the error's own prefix, it is added once for the whole list
+fn reason(error: MainProcessError) -> String {
+    match error {
+        BadContentSchema(reason) => reason,
+        other => other.to_string(),
+    }
+}
+
+fn schema_violations(name: &str, metadata: &Metadata, assertions: &[Assertion]) -> Vec<String> {
+    let mut violations = Violations::default();
+    violations.template("name", name);
+
+    if metadata.properties.is_empty() {
+        violations.push("metadata.properties", "must declare at least one property");
+    }
+    for (i, property) in metadata.properties.iter().enumerate() {
+        let path = format!("metadata.properties[{}]", i);
+        violations.key(&format!("{}.key", path), &property.key);
+        if let Some(name) = &property.name {
+            violations.template(&format!("{}.name", path), name);
+        }
+        violations.template(&format!("{}.description", path), &property.description);
+        if !is_valid_mime(&property.mime) {
+            violations.push(
+                &format!("{}.mime", path),
+                format!("'{}' is not a MIME type like 'image/png'", property.mime),
+            );
+        }
+        // the first tag becomes the IP type
+        if property.tags.is_empty() {
+            violations.push(&format!("{}.tags", path), "must list at least one tag");
+        }
+    }
+
+    match &metadata.owner {
+        Some(owner) => violations.key("metadata.owner", owner),
+        None if metadata.creators.is_empty() => {
+            violations.push("metadata", "must declare an owner or a list of creators")
+        }
+        None => {}
+    }
+    for (i, creator) in metadata.creators.iter().enumerate() {
+        violations.key(&format!("metadata.creators[{}].name", i), &creator.name);
+        if !(1..=100).contains(&creator.contribution_percent) {
+            violations.push(
+                &format!("metadata.creators[{}].contribution_percent", i),
+                "must be between 1 and 100",
+            );
+        }
+    }
+    // shares out of range were listed above, summing them could overflow
+    if !metadata.creators.is_empty()
+        && metadata
+            .creators
+            .iter()
+            .all(|creator| (1..=100).contains(&creator.contribution_percent))
+    {
+        let total: i32 = metadata
+            .creators
+            .iter()
+            .map(|creator| creator.contribution_percent)
+            .sum();
+        if total != 100 {
+            violations.push(
+                "metadata.creators",
+                format!("contribution percents add up to {}, not 100", total),
+            );
+        }
+    }
+
+    for (i, attribute) in metadata.attributes.iter().enumerate() {
+        violations.key(&format!("metadata.attributes[{}].path", i), &attribute.path);
+    }
+    for key in metadata.transforms.keys() {
+        violations.key(&format!("metadata.transforms[\"{}\"]", key), key);
+    }
+
+    for (i, assertion) in assertions.iter().enumerate() {
+        violations.key(&format!("assertions[{}].key", i), &assertion.key);
+        if let Check::Matches(pattern) = &assertion.check {
+            if let Err(e) = Regex::new(pattern) {
+                violations.push(&format!("assertions[{}].matches", i), e);
+            }
+        }
+    }
+
+    violations.0
+}
+
+// `type/subtype` made of RFC 6838 name characters, parameters are allowed
+fn is_valid_mime(mime: &str) -> bool {
+    let essence = mime.split(';').next().unwrap_or_default().trim();
+    let is_name = |part: &str| {
+        !part.is_empty()
+            && part
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$&-^_.+".contains(&b))
+    };
+    matches!(essence.split_once('/'), Some((kind, subtype)) if is_name(kind) && is_name(subtype))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X_POST: &str = include_str!("../schemas/x-post.json");
+
+    fn edited(edit: impl FnOnce(&mut serde_json::Value)) -> String {
+        let mut document: serde_json::Value = serde_json::from_str(X_POST).unwrap();
+        edit(&mut document);
+        document.to_string()
+    }
+
+    #[test]
+    fn test_valid_schema_parses() {
+        let definition = parse_strict::<SchemaDefinition>(X_POST).unwrap();
+        assert_eq!(definition.reference(), "x-post@1");
+
+        // the published JSON Schema is as strict as the parser
+        let schema = serde_json::to_value(schemars::schema_for!(SchemaDefinition)).unwrap();
+        assert_eq!(schema["additionalProperties"], false);
+        assert!(schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&"metadata".into()));
+    }
+
+    #[test]
+    fn test_unknown_and_mistyped_fields_name_their_path() {
+        let error = parse_strict::<SchemaDefinition>(&edited(|document| {
+            document["metadata"]["properties"][0]["mimetype"] = "text/plain".into();
+        }))
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("metadata.properties[0]"));
+        assert!(error.contains("mimetype"));
+
+        let error = parse_strict::<SchemaDefinition>(&edited(|document| {
+            document["metadata"]["attributes"][1]["type"] = "decimal".into();
+        }))
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("metadata.attributes[1].type"));
+    }
+
+    #[test]
+    fn test_every_violation_is_listed() {
+        let error = parse_strict::<SchemaDefinition>(&edited(|document| {
+            let property = &mut document["metadata"]["properties"][0];
+            property["tags"] = serde_json::json!([]);
+            property["mime"] = "plain text".into();
+            property["description"] = "{{received data}}".into();
+            document["metadata"]["owner"] = "owner".into();
+            document["assertions"][1]["matches"] = "(".into();
+            let creator = serde_json::json!({
+                "name": "received>user.name",
+                "address": "0x0000000000000000000000000000000000000001",
+                "contribution_percent": i32::MAX
+            });
+            document["metadata"]["creators"] = serde_json::json!([creator, creator]);
+        }))
+        .unwrap_err()
+        .to_string();
+
+        assert!(error.contains("metadata.properties[0].tags: must list at least one tag"));
+        assert!(error.contains("metadata.properties[0].mime: 'plain text' is not a MIME type"));
+        assert!(error.contains("metadata.properties[0].description: Invalid key format"));
+        assert!(error.contains("metadata.owner: Invalid key format"));
+        assert!(error.contains("assertions[1].matches"));
+        assert!(
+            error.contains("metadata.creators[1].contribution_percent: must be between 1 and 100")
+        );
+    }
+
+    #[test]
+    fn test_mime_types() {
+        assert!(is_valid_mime("image/png"));
+        assert!(is_valid_mime("application/vnd.api+json; charset=utf-8"));
+        assert!(!is_valid_mime("image"));
+        assert!(!is_valid_mime("image/"));
+        assert!(!is_valid_mime("image/p ng"));
+    }
+}
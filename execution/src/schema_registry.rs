@@ -0,0 +1,221 @@
+use alloy::primitives::Address;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::{
+    assertion::Assertion,
+    error::MainProcessError::{self, BadContentSchema, UnknownSchema},
+    schema_check::parse_strict,
+    utils::{ContentSchema, Metadata},
+};
+
+/// A content schema as published in the registry
+///
+/// Holds everything but the fields each registration provides, see [`SchemaDefinition::bind`].
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaDefinition {
+    /// Registry ID, requests reference the schema as `id@version`
+    pub id: String,
+    pub version: u32,
+    pub category: String,
+    pub source: String,
+    pub url: String,
+    pub metadata: Metadata,
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+    pub name: String,
+}
+
+impl SchemaDefinition {
+    /// Returns the `id@version` reference that names exactly this schema.
+    pub fn reference(&self) -> String {
+        format!("{}@{}", self.id, self.version)
+    }
+
+    /// Completes the schema with the fields of a single registration.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address the IP is registered to.
+    /// * `app_id` - The app submitting the registration.
+    /// * `submitter` - The address submitting the registration.
+    pub fn bind(&self, address: Address, app_id: String, submitter: Address) -> ContentSchema {
+        ContentSchema {
+            category: self.category.clone(),
+            source: self.source.clone(),
+            url: self.url.clone(),
+            metadata: self.metadata.clone(),
+            assertions: self.assertions.clone(),
+            name: self.name.clone(),
+            address,
+            app_id,
+            submitter,
+        }
+    }
+}
+
+/// Content schemas this node registers IP with, keyed by ID and version
+#[derive(Debug, Default)]
+pub struct SchemaRegistry {
+    schemas: BTreeMap<String, BTreeMap<u32, SchemaDefinition>>,
+}
+
+impl SchemaRegistry {
+    /// Creates a registry from schema definitions, refusing duplicate `id@version` pairs.
+    ///
+    /// # Arguments
+    ///
+    /// * `definitions` - The schemas to register.
+    pub fn new(definitions: Vec<SchemaDefinition>) -> Result<Self, MainProcessError> {
+        let mut registry = SchemaRegistry::default();
+        for definition in definitions {
+            registry.insert(definition)?;
+        }
+        Ok(registry)
+    }
+
+    /// Loads every `.json` file found directly in a directory, each holding one schema.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to scan.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, MainProcessError> {
+        let dir = dir.as_ref();
+        let read_failed = |path: &Path, e: std::io::Error| {
+            BadContentSchema(format!("Failed to read '{}': {}", path.display(), e))
+        };
+
+        let mut paths = fs::read_dir(dir)
+            .map_err(|e| read_failed(dir, e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+            .collect::<Vec<_>>();
+        // a stable load order keeps duplicate errors deterministic
+        paths.sort();
+
+        let mut definitions = Vec::with_capacity(paths.len());
+        for path in paths {
+            let contents = fs::read_to_string(&path).map_err(|e| read_failed(&path, e))?;
+            definitions.push(parse_strict(&contents).map_err(|e| match e {
+                BadContentSchema(reason) => {
+                    BadContentSchema(format!("{}: {}", path.display(), reason))
+                }
+                other => other,
+            })?);
+        }
+
+        Self::new(definitions)
+    }
+
+    fn insert(&mut self, definition: SchemaDefinition) -> Result<(), MainProcessError> {
+        let id = &definition.id;
+        if id.is_empty() || id.contains(['@', '/']) || id.contains(char::is_whitespace) {
+            return Err(BadContentSchema(format!(
+                "Schema ID '{}' must be non-empty without '@', '/' or whitespace",
+                definition.id
+            )));
+        }
+
+        let versions = self.schemas.entry(definition.id.clone()).or_default();
+        if versions.contains_key(&definition.version) {
+            return Err(BadContentSchema(format!(
+                "Schema '{}' is defined more than once",
+                definition.reference()
+            )));
+        }
+        versions.insert(definition.version, definition);
+        Ok(())
+    }
+
+    /// Looks up a schema by `id@version`, or the latest version when only the ID is given.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The schema reference, e.g. `x-post@2` or `x-post`.
+    pub fn get(&self, reference: &str) -> Result<&SchemaDefinition, MainProcessError> {
+        let reference = reference.trim();
+        let unknown = || UnknownSchema(reference.to_string());
+
+        let (id, version) = match reference.split_once('@') {
+            Some((id, version)) => (id, Some(version.parse::<u32>().map_err(|_| unknown())?)),
+            None => (reference, None),
+        };
+        let versions = self.schemas.get(id).ok_or_else(unknown)?;
+        match version {
+            Some(version) => versions.get(&version),
+            None => versions.values().next_back(),
+        }
+        .ok_or_else(unknown)
+    }
+
+    /// Lists every schema, ordered by ID and then version.
+    pub fn list(&self) -> Vec<&SchemaDefinition> {
+        self.schemas
+            .values()
+            .flat_map(|versions| versions.values())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X_POST: &str = include_str!("../schemas/x-post.json");
+
+    fn definition(id: &str, version: u32) -> SchemaDefinition {
+        let mut definition: SchemaDefinition = serde_json::from_str(X_POST).unwrap();
+        definition.id = id.to_string();
+        definition.version = version;
+        definition
+    }
+
+    #[test]
+    fn test_lookup_by_reference() {
+        let registry = SchemaRegistry::new(vec![
+            definition("x-post", 2),
+            definition("x-post", 1),
+            definition("x-profile", 1),
+        ])
+        .unwrap();
+
+        assert_eq!(registry.get("x-post@1").unwrap().reference(), "x-post@1");
+        // the bare ID resolves to the latest version
+        assert_eq!(registry.get("x-post").unwrap().reference(), "x-post@2");
+        assert!(matches!(registry.get("x-post@3"), Err(UnknownSchema(_))));
+        assert!(matches!(
+            registry.get("x-post@latest"),
+            Err(UnknownSchema(_))
+        ));
+        assert!(matches!(registry.get("tiktok"), Err(UnknownSchema(_))));
+
+        let listed: Vec<String> = registry.list().iter().map(|d| d.reference()).collect();
+        assert_eq!(listed, vec!["x-post@1", "x-post@2", "x-profile@1"]);
+    }
+
+    #[test]
+    fn test_invalid_registries() {
+        assert!(
+            SchemaRegistry::new(vec![definition("x-post", 1), definition("x-post", 1)]).is_err()
+        );
+        assert!(SchemaRegistry::new(vec![definition("x@post", 1)]).is_err());
+        assert!(SchemaRegistry::new(vec![definition(" ", 1)]).is_err());
+    }
+
+    #[test]
+    fn test_bundled_schemas_load() {
+        let registry =
+            SchemaRegistry::from_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/schemas")).unwrap();
+        let schema = registry.get("x-post@1").unwrap().bind(
+            Address::ZERO,
+            "ap3sd1234567890".into(),
+            Address::ZERO,
+        );
+
+        assert_eq!(schema.url, "api.x.com");
+        assert_eq!(schema.app_id, "ap3sd1234567890");
+        assert_eq!(schema.assertions.len(), 2);
+    }
+}
@@ -1,9 +1,9 @@
-use crate::{
-    error::MainProcessError,
-    service::{
-        __path_register_ip_from_transcript, register_ip_from_transcript, IPAMeta, IPAttribute,
-        IPCreator, IPMedia, NFTMeta, ProofRequest, ProofofTask,
-    },
+use crate::service::{
+    __path_content_schema_json_schema, __path_get_job, __path_get_schema, __path_list_schemas,
+    __path_preview_registration, __path_register_ip_from_transcript, content_schema_json_schema,
+    get_job, get_schema, list_schemas, preview_registration, register_ip_from_transcript,
+    resume_jobs, IPAMeta, IPAttribute, IPCreator, IPMedia, JobAccepted, NFTMeta, ProofRequest,
+    ProofofTask, RegistrationPreview,
 };
 
 use axum::{
@@ -12,22 +12,53 @@ use axum::{
     routing::{get, post},
     Router,
 };
+use execution::{
+    config::{ExecutionConfig, ForgeConfig, Service},
+    error::MainProcessError,
+    jobs::{Job, JobStatus, JobStore, StageName, StageState, StageStatus},
+    schema_registry::SchemaRegistry,
+};
 use eyre::Report;
+use std::sync::Arc;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+/// State shared by every request handler
+pub struct AppState {
+    /// Settings shared with the validation service, trusted notaries and proof policy included
+    pub config: ForgeConfig,
+    /// Settings of the registrar, Pinata and Othentic adapters
+    pub adapters: ExecutionConfig,
+    /// Content schemas requests may register IP with
+    pub schemas: SchemaRegistry,
+    /// Registration jobs and their progress, persisted across restarts
+    pub jobs: JobStore,
+}
+
 // Define API documentation
 #[derive(OpenApi)]
 #[openapi(
     paths(
         root,
         health_check,
-        register_ip_from_transcript
+        register_ip_from_transcript,
+        get_job,
+        preview_registration,
+        list_schemas,
+        get_schema,
+        content_schema_json_schema
     ),
     components(
         schemas(
             ProofRequest,
+            JobAccepted,
+            Job,
+            JobStatus,
+            StageState,
+            StageName,
+            StageStatus,
             ProofofTask,
+            RegistrationPreview,
             IPCreator,
             IPMedia,
             IPAttribute,
@@ -37,7 +68,8 @@ use utoipa_swagger_ui::SwaggerUi;
     ),
     tags(
         (name = "General", description = "Get data about this execution node."),
-        (name = "Task", description = "Perform action on this execution node.")
+        (name = "Task", description = "Perform action on this execution node."),
+        (name = "Schema", description = "Browse the content schemas this node registers IP with.")
     )
 )]
 struct ApiDoc;
@@ -73,14 +105,61 @@ async fn health_check() -> impl IntoResponse {
 }
 
 pub async fn run_server() -> Result<(), MainProcessError> {
+    let config = ForgeConfig::load(Service::Execution)
+        .map_err(|e| MainProcessError::Unexpected(Report::new(e)))?;
+    let adapters = config
+        .execution()
+        .map_err(|e| MainProcessError::Unexpected(Report::new(e)))?;
+    let trust_store = &config.trust_store;
+    println!(
+        "Trusting {} notary key(s): {:?}.",
+        trust_store.notaries().len(),
+        trust_store
+            .notaries()
+            .iter()
+            .map(|notary| notary.label.as_str())
+            .collect::<Vec<_>>()
+    );
+    let schemas = match &config.schema_dir {
+        Some(dir) => SchemaRegistry::from_dir(dir)?,
+        None => SchemaRegistry::default(),
+    };
+    println!(
+        "Serving {} content schema(s): {:?}.",
+        schemas.list().len(),
+        schemas
+            .list()
+            .iter()
+            .map(|schema| schema.reference())
+            .collect::<Vec<_>>()
+    );
+    let jobs = JobStore::open(&config.job_store)?;
+    let listen_address = config.listen_address;
+    let state = Arc::new(AppState {
+        config,
+        adapters,
+        schemas,
+        jobs,
+    });
+    resume_jobs(&state)?;
+
     let router = Router::new()
         .route("/", get(root))
         .route("/healthcheck", get(health_check))
         .route("/register", post(register_ip_from_transcript))
+        .route("/register/preview", post(preview_registration))
+        .route("/jobs/:id", get(get_job))
+        .route("/schemas", get(list_schemas))
+        .route("/schemas/:id", get(get_schema))
+        .route(
+            "/schema/content-schema.json",
+            get(content_schema_json_schema),
+        )
         // Add Swagger UI routes
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()));
+        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:2077")
+    let listener = tokio::net::TcpListener::bind(listen_address)
         .await
         .map_err(|e| MainProcessError::Unexpected(Report::new(e)))?;
 
@@ -1,19 +1,40 @@
-use std::error::Error;
+use std::collections::BTreeMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use crate::error::MainProcessError;
-use crate::utils::{get_content_data, parse_content_json, ContentSchema, Input, PropertyType};
+use crate::server::AppState;
+use crate::stages::{
+    Draft, Extract, PublishDa, RegisterOnchain, SubmitTask, UploadMedia, UploadMetadata, Verify,
+};
+use execution::assertion::check_assertions;
+use execution::error::MainProcessError;
+use execution::jobs::{presentation_hash, Job, StageStatus, Submission};
+use execution::pipeline::{Pipeline, Stage};
+use execution::schema_registry::SchemaDefinition;
+use execution::template::{self, render};
+use execution::transform::Transforms;
+use execution::utils::{
+    extract_value, get_schema_attributes, get_schema_creators, ContentSchema, Input, MediaSlots,
+    Property, PropertyType,
+};
 
-use alloy::primitives::{Address, FixedBytes};
-use axum::{http::StatusCode, response::IntoResponse, Json};
-use eigenda_adapter::publish_blob;
-use othentic::{init_config, send_task};
+use alloy::primitives::{keccak256, Address, FixedBytes};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
-use verifier::{verify_proof_from_json, VerificationResult};
+use uuid::Uuid;
+use verifier::{
+    to_compact_base64, verify_proof_from_bytes, NotaryTrustStore, VerificationPolicy,
+    VerificationResult, VerifierError,
+};
 
 // Serialization: Convert Address to hex string
-fn serialize_address<S>(address: &Address, serializer: S) -> Result<S::Ok, S::Error>
+pub(crate) fn serialize_address<S>(address: &Address, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
@@ -22,7 +43,7 @@ where
 }
 
 // Deserialization: Parse hex string to Address
-fn deserialize_address<'de, D>(deserializer: D) -> Result<Address, D::Error>
+pub(crate) fn deserialize_address<'de, D>(deserializer: D) -> Result<Address, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -31,7 +52,10 @@ where
 }
 
 // Serialization: Convert FixedBytes<32> to hex string
-fn serialize_fixed_bytes<S>(bytes: &FixedBytes<32>, serializer: S) -> Result<S::Ok, S::Error>
+pub(crate) fn serialize_fixed_bytes<S>(
+    bytes: &FixedBytes<32>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
@@ -40,7 +64,7 @@ where
 }
 
 // Deserialization: Parse hex string to FixedBytes<32>
-fn deserialize_fixed_bytes<'de, D>(deserializer: D) -> Result<FixedBytes<32>, D::Error>
+pub(crate) fn deserialize_fixed_bytes<'de, D>(deserializer: D) -> Result<FixedBytes<32>, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -48,17 +72,42 @@ where
     FixedBytes::<32>::from_str(&s).map_err(serde::de::Error::custom)
 }
 
+/// Header clients set to make retrying a registration safe
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
 /// Request body for proof registration
-#[derive(Deserialize, utoipa::ToSchema)]
+#[derive(Serialize, Deserialize, utoipa::ToSchema)]
 pub struct ProofRequest {
-    /// The transcript proof string
+    /// The transcript proof, either a JSON presentation or a base64 encoded bincode presentation
     pub transcript_proof: String,
-    /// The schema string
-    pub schema: String,
+    /// The registered schema to use, `id@version` or just the ID for its latest version
+    pub schema_id: String,
+    /// Address the IP is registered to
+    #[serde(
+        serialize_with = "serialize_address",
+        deserialize_with = "deserialize_address"
+    )]
+    pub address: Address,
+    /// App the registration is submitted for
+    pub app_id: String,
+    /// Address submitting the registration
+    #[serde(
+        serialize_with = "serialize_address",
+        deserialize_with = "deserialize_address"
+    )]
+    pub submitter: Address,
+}
+
+/// Response to a queued registration
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct JobAccepted {
+    /// ID of the job, its progress is reported at `/jobs/{id}`
+    #[schema(value_type = String)]
+    pub job_id: Uuid,
 }
 
 /// IP Creator information
-#[derive(Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Clone, Serialize, Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct IPCreator {
     /// Name of the creator
@@ -74,7 +123,7 @@ pub struct IPCreator {
 }
 
 /// IP Media information
-#[derive(Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Clone, Serialize, Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct IPMedia {
     /// Name of the media
@@ -86,7 +135,7 @@ pub struct IPMedia {
 }
 
 /// IP Attribute information
-#[derive(Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Clone, Serialize, Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct IPAttribute {
     /// Attribute key
@@ -96,7 +145,7 @@ pub struct IPAttribute {
 }
 
 /// IP Metadata
-#[derive(Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Clone, Serialize, Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct IPAMeta {
     /// Title of the IP
@@ -116,7 +165,7 @@ pub struct IPAMeta {
 }
 
 /// NFT Metadata
-#[derive(Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct NFTMeta {
     /// Name of the NFT
     pub name: String,
@@ -132,10 +181,36 @@ pub struct NFTMeta {
     pub text_content: Option<String>,
 }
 
+/// What a registration would produce, returned without uploading or registering anything
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RegistrationPreview {
+    /// The exact schema the IP would be registered with, as `id@version`
+    pub schema_id: String,
+    /// Values extracted for every content key the schema reads, after transforms
+    #[schema(value_type = Object)]
+    pub values: BTreeMap<String, Value>,
+    /// IP metadata as it would be uploaded and sent to the registry contract
+    pub ip_metadata: IPAMeta,
+    /// NFT metadata as it would be uploaded and sent to the registry contract
+    pub nft_metadata: NFTMeta,
+    /// keccak256 of the IP metadata JSON, as the registry contract computes it
+    #[serde(serialize_with = "serialize_fixed_bytes")]
+    #[schema(value_type = String)]
+    pub ip_metadata_hash: FixedBytes<32>,
+    /// keccak256 of the NFT metadata JSON, as the registry contract computes it
+    #[serde(serialize_with = "serialize_fixed_bytes")]
+    #[schema(value_type = String)]
+    pub nft_metadata_hash: FixedBytes<32>,
+    /// False when the schema pins media, their IPFS URLs and so the hashes differ once uploaded
+    pub exact_hashes: bool,
+    /// Secrets the proof leaves unredacted, published along with it on registration
+    pub warnings: Vec<String>,
+}
+
 /// Proof of Task response
-#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct ProofofTask {
-    /// The transcript proof
+    /// The transcript proof as a base64 encoded bincode presentation
     pub transcript_proof: String,
     /// Transaction hash
     #[serde(
@@ -149,96 +224,112 @@ pub struct ProofofTask {
         deserialize_with = "deserialize_address"
     )]
     pub ip_id: Address,
+    /// The exact schema the IP was registered with, as `id@version`
+    pub schema_id: String,
+    /// Address the IP was registered to
+    #[serde(
+        serialize_with = "serialize_address",
+        deserialize_with = "deserialize_address"
+    )]
+    pub address: Address,
+    /// App the registration was submitted for
+    pub app_id: String,
+    /// Address that submitted the registration
+    #[serde(
+        serialize_with = "serialize_address",
+        deserialize_with = "deserialize_address"
+    )]
+    pub submitter: Address,
+    /// Time at which the execution node verified the transcript proof
+    #[schema(value_type = String, format = DateTime)]
+    pub verified_at: chrono::DateTime<chrono::Utc>,
 }
 
-async fn upload_file_to_ipfs(
-    transcript: &VerificationResult,
+/// Builds the IP and NFT metadata of a registration around the given media.
+///
+/// # Arguments
+///
+/// * `content` - The schema bound to the registration.
+/// * `title` - The rendered title of the IP.
+/// * `media` - One entry per schema property, in order.
+/// * `transcript` - The verified transcript.
+pub(crate) fn build_metadata(
     content: &ContentSchema,
-) -> Result<String, MainProcessError> {
-    let file_up = match content.metadata.property.property_type {
-        PropertyType::File => {
-            pinata::upload_file_from_url(pinata::FileUploadParams {
-                file_url: get_content_data(transcript, &content.metadata.property.key).unwrap(),
-                file_name: content.metadata.property.key.clone(),
-                file_type: content.metadata.property.mime.clone(),
-            })
-            .await
-            .map_err(|e| MainProcessError::BadFileUse(e.to_string()))
-            .unwrap()
-            .ipfs_hash
-        }
-        PropertyType::Json => {
-            pinata::upload_json(
-                Value::from_str(
-                    &get_content_data(transcript, &content.metadata.property.key).unwrap(),
-                )
-                .unwrap(),
-            )
-            .await
-            .map_err(|e| MainProcessError::BadFileUse(e.to_string()))
-            .unwrap()
-            .ipfs_hash
-        }
-        PropertyType::Url => get_content_data(transcript, &content.metadata.property.key)
-            .map_err(|e| MainProcessError::BadFileUse(e.to_string()))?,
-    };
+    title: &str,
+    media: Vec<IPMedia>,
+    transcript: &VerificationResult,
+) -> Result<(IPAMeta, NFTMeta), MainProcessError> {
+    let properties = &content.metadata.properties;
+    let ip_type = properties
+        .iter()
+        .find_map(|property| property.tags.first())
+        .ok_or_else(|| {
+            MainProcessError::BadContentSchema("At least one property needs a tag".to_string())
+        })?
+        .clone();
 
-    match content.metadata.property.property_type {
-        PropertyType::File | PropertyType::Json => Ok(format!("https://ipfs.io/ipfs/{}", file_up)),
-        PropertyType::Url => Ok(file_up),
+    let mut tags: Vec<String> = Vec::new();
+    for tag in properties.iter().flat_map(|property| &property.tags) {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
     }
-}
 
-async fn create_and_upload_metadata(
-    content: &ContentSchema,
-    file_url: &str,
-    transcript: &VerificationResult,
-) -> Result<(String, String, String, String), MainProcessError> {
-    let ipameta = IPAMeta {
-        title: content.name.clone(),
-        media: vec![IPMedia {
-            name: content.name.clone(),
-            mimetype: content.metadata.property.mime.clone(),
-            url: file_url.to_string(),
-        }],
-        description: content.metadata.property.description.clone(),
-        attributes: vec![IPAttribute {
-            key: "Source".to_string(),
-            value: Value::String(transcript.server_name.as_str().to_string()),
-        }],
-        ip_type: content.metadata.property.tags[0].clone(),
-        creators: vec![IPCreator {
-            address: content.address,
-            contribution_percent: 100,
-            name: get_content_data(transcript, &content.metadata.owner).unwrap(),
-        }],
-        tags: content.metadata.property.tags.clone(),
-    };
+    let mut attributes = vec![IPAttribute {
+        key: "Source".to_string(),
+        value: Value::String(transcript.server_name.as_str().to_string()),
+    }];
+    attributes.extend(
+        get_schema_attributes(transcript, content)?
+            .into_iter()
+            .map(|(key, value)| IPAttribute { key, value }),
+    );
 
-    let nftmeta = NFTMeta {
-        name: content.name.clone(),
-        description: content.metadata.property.description.clone(),
-        image_url: Some(file_url.to_string()),
+    let description = render(
+        &properties[0].description,
+        transcript,
+        &content.metadata.transforms,
+    )?;
+
+    let mut nftmeta = NFTMeta {
+        name: title.to_string(),
+        description: description.clone(),
+        image_url: None,
         audio_url: None,
         animation_url: None,
         text_content: None,
     };
 
-    let ipameta_up =
-        pinata::upload_json(Value::from_str(&serde_json::to_string(&ipameta).unwrap()).unwrap())
-            .await
-            .map_err(|e| MainProcessError::BadRequest(e.to_string()))?; // same deal
-    let nftmeta_up =
-        pinata::upload_json(Value::from_str(&serde_json::to_string(&nftmeta).unwrap()).unwrap())
-            .await
-            .map_err(|e| MainProcessError::BadRequest(e.to_string()))?; // same deal
-
-    Ok((
-        serde_json::to_string(&ipameta).unwrap(),
-        serde_json::to_string(&nftmeta).unwrap(),
-        format!("https://ipfs.io/ipfs/{}", ipameta_up.ipfs_hash),
-        format!("https://ipfs.io/ipfs/{}", nftmeta_up.ipfs_hash),
-    ))
+    let mut ipameta = IPAMeta {
+        title: title.to_string(),
+        media: Vec::new(),
+        description,
+        attributes,
+        ip_type,
+        creators: get_schema_creators(transcript, content)?
+            .into_iter()
+            .map(|creator| IPCreator {
+                name: creator.name,
+                address: creator.address,
+                contribution_percent: creator.contribution_percent,
+            })
+            .collect(),
+        tags,
+    };
+    attach_media(&mut ipameta, &mut nftmeta, media);
+
+    Ok((ipameta, nftmeta))
+}
+
+/// Sets the media of built metadata, replacing the ones it had.
+pub(crate) fn attach_media(ipameta: &mut IPAMeta, nftmeta: &mut NFTMeta, media: Vec<IPMedia>) {
+    // the first image, audio and video entries fill the NFT's media slots
+    let slots = MediaSlots::from_mimes(media.iter().map(|media| media.mimetype.as_str()));
+    let slot_url = |slot: Option<usize>| slot.map(|index| media[index].url.clone());
+    nftmeta.image_url = slot_url(slots.image);
+    nftmeta.audio_url = slot_url(slots.audio);
+    nftmeta.animation_url = slot_url(slots.animation);
+    ipameta.media = media;
 }
 
 #[utoipa::path(
@@ -246,23 +337,293 @@ async fn create_and_upload_metadata(
     path = "/register",
     tag = "Task",
     request_body = ProofRequest,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retrying with the same key returns the job the first request created")
+    ),
     responses(
-        (status = 200, description = "Successfully registered IP", body = ProofofTask),
+        (status = 200, description = "The presentation or the key already has a job, nothing was queued", body = JobAccepted),
+        (status = 202, description = "Registration queued, poll the job for its progress", body = JobAccepted),
         (status = 400, description = "Bad request - Invalid proof or schema"),
+        (status = 404, description = "No schema matches the reference"),
+        (status = 409, description = "The key was already used for another registration"),
         (status = 500, description = "Internal server error")
     )
 )]
 pub async fn register_ip_from_transcript(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(body): Json<ProofRequest>,
 ) -> Result<impl IntoResponse, MainProcessError> {
-    let content = parse_content_json(body.schema.as_str()).unwrap();
+    // refuse what could never be registered before queueing anything
+    let Prepared { definition, .. } = prepare(&state, &body, chrono::Utc::now())?;
 
-    let transcript = verify(Input {
-        transcript_proof: body.transcript_proof.clone(),
-    })
-    .map_err(|_| {
-        MainProcessError::BadTranscriptProof("Transcript verification failed".to_string())
+    let idempotency_key = headers
+        .get(IDEMPOTENCY_KEY)
+        .map(|key| key.to_str())
+        .transpose()
+        .map_err(|_| MainProcessError::BadRequest("Invalid Idempotency-Key header".to_string()))?;
+    // the same proof registers the same IP once, however it was encoded or who sends it
+    let presentation = presentation_hash(&body.transcript_proof, &definition.reference())?;
+    // a resumed job keeps the version it was accepted with, whatever was deployed since
+    let body = ProofRequest {
+        schema_id: definition.reference(),
+        ..body
+    };
+
+    match state.jobs.create(&body, presentation, idempotency_key)? {
+        Submission::Created(job) => {
+            let job_id = job.id;
+            println!("Queued registration job {}.", job_id);
+            tokio::spawn(run_job(state, job));
+            Ok((StatusCode::ACCEPTED, Json(JobAccepted { job_id })))
+        }
+        Submission::Existing(job) => {
+            println!("Registration already handled by job {}.", job.id);
+            Ok((StatusCode::OK, Json(JobAccepted { job_id: job.id })))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    tag = "Task",
+    params(
+        ("id" = String, Path, description = "Job ID returned by `/register`")
+    ),
+    responses(
+        (status = 200, description = "The job and the status of each of its stages", body = Job),
+        (status = 404, description = "No job has this ID")
+    )
+)]
+pub async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, MainProcessError> {
+    Ok(Json(state.jobs.get(id)?))
+}
+
+/// Picks up the jobs a previous run left unfinished or stalled, each from its last completed
+/// stage.
+///
+/// A job stopped in the middle of a stage that can't safely run twice, like the on-chain
+/// registration, is failed instead so the operator can check whether it took effect.
+pub fn resume_jobs(state: &Arc<AppState>) -> Result<(), MainProcessError> {
+    for mut job in state.jobs.unfinished()? {
+        let interrupted = job
+            .stages
+            .iter()
+            .find(|stage| stage.status == StageStatus::Running)
+            .map(|stage| stage.stage);
+        match interrupted {
+            Some(stage) if !stage.is_repeatable() => {
+                println!(
+                    "Registration job {} stopped during {}, not resuming it.",
+                    job.id, stage
+                );
+                job.fail(&MainProcessError::Unexpected(eyre::eyre!(
+                    "The node stopped before the outcome was recorded, check it before registering again"
+                )));
+                state.jobs.save(&mut job)?;
+            }
+            _ => {
+                println!("Resuming registration job {}.", job.id);
+                tokio::spawn(run_job(state.clone(), job));
+            }
+        }
+    }
+    Ok(())
+}
+
+// runs a registration in the background, the outcome is only reported through the job
+async fn run_job(state: Arc<AppState>, mut job: Job) {
+    let outcome = register(&state, &mut job).await.and_then(|proof| {
+        serde_json::to_value(&proof)
+            .map_err(|e| eyre::eyre!("Failed to serialize the proof of task: {}", e).into())
+    });
+    match outcome {
+        Ok(proof) => {
+            println!("Registration job {} succeeded.", job.id);
+            job.succeed(proof);
+        }
+        // the IP exists on chain, publishing and submitting it are tried again
+        Err(e) if job.is_registered() => {
+            println!(
+                "Registration job {} stalled, it resumes when the node restarts: {}",
+                job.id, e
+            );
+            job.stall(&e);
+        }
+        Err(e) => {
+            println!("Registration job {} failed: {}", job.id, e);
+            job.fail(&e);
+        }
+    }
+    if let Err(e) = state.jobs.save(&mut job) {
+        eprintln!("Failed to save job {}: {}", job.id, e);
+    }
+}
+
+// stages that finished before a restart are skipped, their journal entries stand in for them
+async fn register(state: &AppState, job: &mut Job) -> Result<ProofofTask, MainProcessError> {
+    let body: ProofRequest = state.jobs.request(job.id)?;
+    let mut pipeline = Pipeline::new(&state.jobs, job);
+
+    // a resumed job is verified again now, validators bound how long ago a task was verified
+    let verified_at = chrono::Utc::now();
+    let verified = pipeline
+        .run(
+            &Verify {
+                state,
+                request: &body,
+            },
+            verified_at,
+        )
+        .await?;
+    let prepared = verified.prepared.ok_or_else(|| {
+        eyre::eyre!("The verified registration was taken from the journal instead of replayed")
     })?;
+    let Prepared {
+        definition,
+        content,
+        transcript,
+        verified_at,
+    } = &*prepared;
+    if definition.reference() != body.schema_id {
+        return Err(eyre::eyre!(
+            "The job was queued for schema {}, not an exact version",
+            body.schema_id
+        )
+        .into());
+    }
+
+    let draft = pipeline
+        .run(
+            &Extract {
+                prepared: &prepared,
+            },
+            (),
+        )
+        .await?;
+    let media = pipeline
+        .run(&UploadMedia { state, content }, draft.clone())
+        .await?;
+    let uploaded = pipeline
+        .run(
+            &UploadMetadata { state, transcript },
+            (draft.clone(), media),
+        )
+        .await?;
+    let registered = pipeline
+        .run(&RegisterOnchain { state, content }, (draft.title, uploaded))
+        .await?;
+
+    let proof = ProofofTask {
+        // JSON presentations are several times larger than bincode, keep the DA blob small
+        transcript_proof: to_compact_base64(body.transcript_proof.as_bytes())?,
+        transaction_hash: registered.transaction_hash,
+        ip_id: registered.ip_id,
+        schema_id: definition.reference(),
+        address: content.address,
+        app_id: content.app_id.clone(),
+        submitter: content.submitter,
+        verified_at: *verified_at,
+    };
+
+    let published = pipeline
+        .run(&PublishDa { state, transcript }, proof.clone())
+        .await?;
+    pipeline.run(&SubmitTask { state }, published).await?;
+
+    Ok(proof)
+}
+
+#[utoipa::path(
+    post,
+    path = "/register/preview",
+    tag = "Task",
+    request_body = ProofRequest,
+    responses(
+        (status = 200, description = "The metadata the registration would produce", body = RegistrationPreview),
+        (status = 400, description = "Bad request - Invalid proof or schema"),
+        (status = 404, description = "No schema matches the reference")
+    )
+)]
+pub async fn preview_registration(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<ProofRequest>,
+) -> Result<impl IntoResponse, MainProcessError> {
+    let prepared = prepare(&state, &body, chrono::Utc::now())?;
+    let Prepared {
+        definition,
+        content,
+        transcript,
+        ..
+    } = &prepared;
+    let transforms = &content.metadata.transforms;
+
+    let mut values = BTreeMap::new();
+    for key in schema_keys(content) {
+        values.insert(key.to_string(), extract_value(transcript, key, transforms)?);
+    }
+
+    // nothing is pinned, media keep the URL or document they would be uploaded from
+    let Draft {
+        ip_metadata,
+        nft_metadata,
+        ..
+    } = Extract {
+        prepared: &prepared,
+    }
+    .run(&())
+    .await?;
+    check_metadata(&state, transcript, &ip_metadata, &nft_metadata)?;
+    let (ip_json, nft_json) = metadata_json(&ip_metadata, &nft_metadata)?;
+
+    Ok(Json(RegistrationPreview {
+        schema_id: definition.reference(),
+        values,
+        // the registry contract hashes the exact JSON strings it is sent
+        ip_metadata_hash: keccak256(ip_json),
+        nft_metadata_hash: keccak256(nft_json),
+        exact_hashes: content
+            .metadata
+            .properties
+            .iter()
+            .all(|property| matches!(property.property_type, PropertyType::Url)),
+        warnings: state.config.sensitive.unredacted_secrets(transcript),
+        ip_metadata,
+        nft_metadata,
+    }))
+}
+
+/// A registration checked against its schema
+pub(crate) struct Prepared<'a> {
+    pub definition: &'a SchemaDefinition,
+    pub content: ContentSchema,
+    pub transcript: VerificationResult,
+    pub verified_at: chrono::DateTime<chrono::Utc>,
+}
+
+// resolves the schema, verifies the transcript and checks it against the schema
+pub(crate) fn prepare<'a>(
+    state: &'a AppState,
+    body: &ProofRequest,
+    verified_at: chrono::DateTime<chrono::Utc>,
+) -> Result<Prepared<'a>, MainProcessError> {
+    let definition = state.schemas.get(&body.schema_id)?;
+    let content = definition.bind(body.address, body.app_id.clone(), body.submitter);
+    state.config.sensitive.check_keys(schema_keys(&content))?;
+
+    // validators re-apply the freshness policy at this time rather than when they see the task
+    let transcript = verify(
+        Input {
+            transcript_proof: body.transcript_proof.clone(),
+        },
+        &state.config.trust_store,
+        &state.config.policy.at(verified_at),
+    )?;
+    println!("Transcript attested by notary '{}'.", transcript.notary);
 
     if content.url != transcript.server_name.as_str() {
         return Err(MainProcessError::BadContentSchema(
@@ -270,48 +631,170 @@ pub async fn register_ip_from_transcript(
         ));
     }
 
-    let file_url = upload_file_to_ipfs(&transcript, &content).await?;
-    let (ipameta, nftmeta, ipameta_url, nftmeta_url) =
-        create_and_upload_metadata(&content, &file_url, &transcript).await?;
-
-    let regip = registrar::register_ip(
-        content.address,
-        content.name,
-        ipameta_url,
-        ipameta,
-        nftmeta_url,
-        nftmeta,
-        content.app_id,
-        content.submitter,
+    if content.metadata.properties.is_empty() {
+        return Err(MainProcessError::BadContentSchema(
+            "The schema must declare at least one property".to_string(),
+        ));
+    }
+
+    // error pages and failed lookups are verified too, the schema decides what may be registered
+    check_assertions(
+        &transcript,
+        &content.assertions,
+        &content.metadata.transforms,
+    )?;
+
+    Ok(Prepared {
+        definition,
+        content,
+        transcript,
+        verified_at,
+    })
+}
+
+// metadata ends up on IPFS and in the registry, neither may carry a credential
+pub(crate) fn check_metadata(
+    state: &AppState,
+    transcript: &VerificationResult,
+    ipameta: &IPAMeta,
+    nftmeta: &NFTMeta,
+) -> Result<(), MainProcessError> {
+    let (ip_json, nft_json) = metadata_json(ipameta, nftmeta)?;
+    state
+        .config
+        .sensitive
+        .check_published(transcript, &ip_json)?;
+    state
+        .config
+        .sensitive
+        .check_published(transcript, &nft_json)
+}
+
+// the exact JSON the registry is sent and hashes
+pub(crate) fn metadata_json(
+    ipameta: &IPAMeta,
+    nftmeta: &NFTMeta,
+) -> Result<(String, String), MainProcessError> {
+    let ip_json = serde_json::to_string(ipameta)
+        .map_err(|e| eyre::eyre!("Failed to serialize the IP metadata: {}", e))?;
+    let nft_json = serde_json::to_string(nftmeta)
+        .map_err(|e| eyre::eyre!("Failed to serialize the NFT metadata: {}", e))?;
+    Ok((ip_json, nft_json))
+}
+
+pub(crate) fn media_name(
+    property: &Property,
+    title: &str,
+    transcript: &VerificationResult,
+    transforms: &Transforms,
+) -> Result<String, MainProcessError> {
+    match &property.name {
+        Some(name) => render(name, transcript, transforms),
+        None => Ok(title.to_string()),
+    }
+}
+
+// every content key the schema reads, placeholders in its templates included
+fn schema_keys(content: &ContentSchema) -> Vec<&str> {
+    let metadata = &content.metadata;
+    let mut templates = vec![content.name.as_str()];
+    let mut keys = Vec::new();
+    for property in &metadata.properties {
+        keys.push(property.key.as_str());
+        templates.extend(property.name.as_deref());
+        templates.push(&property.description);
+    }
+    keys.extend(metadata.owner.as_deref());
+    keys.extend(
+        metadata
+            .creators
+            .iter()
+            .map(|creator| creator.name.as_str()),
+    );
+    keys.extend(
+        metadata
+            .attributes
+            .iter()
+            .map(|attribute| attribute.path.as_str()),
+    );
+    keys.extend(
+        content
+            .assertions
+            .iter()
+            .map(|assertion| assertion.key.as_str()),
+    );
+    for template in templates {
+        keys.extend(template::keys(template).unwrap_or_default());
+    }
+    keys
+}
+
+#[utoipa::path(
+    get,
+    path = "/schemas",
+    tag = "Schema",
+    responses(
+        (status = 200, description = "Every schema version this node registers IP with", body = [Object])
     )
-    .await
-    .map_err(|e| MainProcessError::BadRequest(e.to_string()))?;
+)]
+pub async fn list_schemas(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(
+        state
+            .schemas
+            .list()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>(),
+    )
+}
 
-    let proof = ProofofTask {
-        transcript_proof: body.transcript_proof,
-        transaction_hash: regip.hash,
-        ip_id: regip.ipid,
-    };
-    let req_id = publish_blob(format!("00{}", serde_json::to_string(&proof).unwrap()))
-        .await
-        .unwrap();
-
-    let private_key =
-        std::env::var("PRIVATE_KEY").expect("PRIVATE_KEY is not set in environment variables");
-    let rpc_url = std::env::var("OTHENTIC_CLIENT_RPC_ADDRESS")
-        .expect("ETH_RPC_URL is not set in environment variables");
-    init_config(private_key, rpc_url);
-    send_task(req_id, 0).await.unwrap();
-
-    Ok((StatusCode::OK, serde_json::to_string(&proof).unwrap()))
+#[utoipa::path(
+    get,
+    path = "/schemas/{id}",
+    tag = "Schema",
+    params(
+        ("id" = String, Path, description = "Schema reference as `id@version`, or an ID for its latest version")
+    ),
+    responses(
+        (status = 200, description = "The schema", body = Object),
+        (status = 404, description = "No schema matches the reference")
+    )
+)]
+pub async fn get_schema(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, MainProcessError> {
+    Ok(Json(state.schemas.get(&id)?.clone()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/schema/content-schema.json",
+    tag = "Schema",
+    responses(
+        (status = 200, description = "JSON Schema that registry content schemas follow", body = Object)
+    )
+)]
+pub async fn content_schema_json_schema() -> impl IntoResponse {
+    Json(schemars::schema_for!(SchemaDefinition))
 }
 
-fn verify(pre_image: Input) -> Result<VerificationResult, Box<dyn Error>> {
-    let result = verify_proof_from_json(pre_image.transcript_proof.as_str())?;
+fn verify(
+    pre_image: Input,
+    trust_store: &NotaryTrustStore,
+    policy: &VerificationPolicy,
+) -> Result<VerificationResult, VerifierError> {
+    let result =
+        verify_proof_from_bytes(pre_image.transcript_proof.as_bytes(), trust_store, policy)?;
     Ok(VerificationResult {
         server_name: result.server_name,
         time: result.time,
+        notary: result.notary,
         sent_data: result.sent_data,
         received_data: result.received_data,
+        sent_authed: result.sent_authed,
+        received_authed: result.received_authed,
+        sent_raw: result.sent_raw,
+        received_raw: result.received_raw,
     })
 }
@@ -0,0 +1,429 @@
+use std::{future::Future, str::FromStr, sync::Arc, time::Duration};
+
+use alloy::primitives::{Address, FixedBytes};
+use chrono::{DateTime, Utc};
+use eigenda_adapter::publish_blob;
+use othentic::send_task;
+use pinata::PinataConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use verifier::VerificationResult;
+
+use execution::{
+    error::MainProcessError,
+    jobs::StageName,
+    pipeline::{Stage, StagePolicy},
+    template::render,
+    utils::{extract_string, ContentSchema, Property, PropertyType},
+};
+
+use crate::{
+    server::AppState,
+    service::{
+        attach_media, build_metadata, check_metadata, deserialize_address, deserialize_fixed_bytes,
+        media_name, metadata_json, prepare, serialize_address, serialize_fixed_bytes, IPAMeta,
+        IPMedia, NFTMeta, Prepared, ProofRequest, ProofofTask,
+    },
+};
+
+/// Output of [`Verify`], the verified registration itself is only kept in memory
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Verified<'a> {
+    pub notary: String,
+    pub verified_at: DateTime<Utc>,
+    #[serde(skip)]
+    pub prepared: Option<Arc<Prepared<'a>>>,
+}
+
+/// Verifies the transcript and checks it against the schema
+pub struct Verify<'a> {
+    pub state: &'a AppState,
+    pub request: &'a ProofRequest,
+}
+
+impl<'a> Stage for Verify<'a> {
+    /// When the proof is judged, the proof of task carries it for validators to judge it again
+    type Input = DateTime<Utc>;
+    type Output = Verified<'a>;
+
+    const NAME: StageName = StageName::Verify;
+    // the transcript every later stage reads isn't journaled
+    const REPLAYED: bool = true;
+
+    fn policy(&self) -> StagePolicy {
+        StagePolicy::ONCE
+    }
+
+    async fn run(&self, verified_at: &DateTime<Utc>) -> Result<Verified<'a>, MainProcessError> {
+        let prepared = prepare(self.state, self.request, *verified_at)?;
+        Ok(Verified {
+            notary: prepared.transcript.notary.clone(),
+            verified_at: prepared.verified_at,
+            prepared: Some(Arc::new(prepared)),
+        })
+    }
+}
+
+/// Output of [`Extract`], media still point at the URL or document they are uploaded from
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Draft {
+    pub title: String,
+    pub ip_metadata: IPAMeta,
+    pub nft_metadata: NFTMeta,
+}
+
+/// Reads everything the registration publishes out of the transcript
+pub struct Extract<'a> {
+    pub prepared: &'a Prepared<'a>,
+}
+
+impl Stage for Extract<'_> {
+    type Input = ();
+    type Output = Draft;
+
+    const NAME: StageName = StageName::Extract;
+
+    fn policy(&self) -> StagePolicy {
+        StagePolicy::ONCE
+    }
+
+    async fn run(&self, _: &()) -> Result<Draft, MainProcessError> {
+        let Prepared {
+            content,
+            transcript,
+            ..
+        } = self.prepared;
+        let transforms = &content.metadata.transforms;
+
+        // names and descriptions may hold placeholders filled from the transcript
+        let title = render(&content.name, transcript, transforms)?;
+        let mut media = Vec::with_capacity(content.metadata.properties.len());
+        for property in &content.metadata.properties {
+            media.push(IPMedia {
+                name: media_name(property, &title, transcript, transforms)?,
+                url: extract_string(transcript, &property.key, transforms)?,
+                mimetype: property.mime.clone(),
+            });
+        }
+        let (ip_metadata, nft_metadata) = build_metadata(content, &title, media, transcript)?;
+
+        Ok(Draft {
+            title,
+            ip_metadata,
+            nft_metadata,
+        })
+    }
+}
+
+/// Output of [`UploadMedia`]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PinnedMedia {
+    pub media: Vec<IPMedia>,
+    /// IPFS hashes pinned for the media, unpinned if the registration fails
+    pub pins: Vec<String>,
+}
+
+/// Pins file and JSON properties to IPFS, URL properties are published as they are
+pub struct UploadMedia<'a> {
+    pub state: &'a AppState,
+    pub content: &'a ContentSchema,
+}
+
+impl Stage for UploadMedia<'_> {
+    type Input = Draft;
+    type Output = PinnedMedia;
+
+    const NAME: StageName = StageName::UploadMedia;
+
+    fn policy(&self) -> StagePolicy {
+        StagePolicy::retrying(3, Duration::from_secs(120))
+    }
+
+    async fn run(&self, draft: &Draft) -> Result<PinnedMedia, MainProcessError> {
+        let mut pinned = PinnedMedia {
+            media: Vec::with_capacity(draft.ip_metadata.media.len()),
+            pins: Vec::new(),
+        };
+        // media were extracted in property order
+        for (property, source) in self
+            .content
+            .metadata
+            .properties
+            .iter()
+            .zip(&draft.ip_metadata.media)
+        {
+            let url = match pin_media(&self.state.adapters.pinata, property, &source.url).await? {
+                Some(ipfs_hash) => {
+                    let url = format!("{}{}", self.state.config.ipfs_gateway, ipfs_hash);
+                    pinned.pins.push(ipfs_hash);
+                    url
+                }
+                None => source.url.clone(),
+            };
+            pinned.media.push(IPMedia {
+                url,
+                ..source.clone()
+            });
+        }
+        Ok(pinned)
+    }
+
+    fn compensate(
+        &self,
+        output: PinnedMedia,
+    ) -> impl Future<Output = Result<(), MainProcessError>> + Send + 'static {
+        unpin_all(self.state.adapters.pinata.clone(), output.pins)
+    }
+}
+
+/// Output of [`UploadMetadata`], the exact JSON sent to the registry included
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UploadedMetadata {
+    pub ip_metadata: String,
+    pub nft_metadata: String,
+    pub ip_metadata_url: String,
+    pub nft_metadata_url: String,
+    /// IPFS hashes of both documents, unpinned if the registration fails
+    pub pins: Vec<String>,
+}
+
+/// Completes the metadata with the pinned media and pins it
+pub struct UploadMetadata<'a> {
+    pub state: &'a AppState,
+    pub transcript: &'a VerificationResult,
+}
+
+impl Stage for UploadMetadata<'_> {
+    type Input = (Draft, PinnedMedia);
+    type Output = UploadedMetadata;
+
+    const NAME: StageName = StageName::UploadMetadata;
+
+    fn policy(&self) -> StagePolicy {
+        StagePolicy::retrying(3, Duration::from_secs(60))
+    }
+
+    async fn run(
+        &self,
+        (draft, pinned): &(Draft, PinnedMedia),
+    ) -> Result<UploadedMetadata, MainProcessError> {
+        let mut ipameta = draft.ip_metadata.clone();
+        let mut nftmeta = draft.nft_metadata.clone();
+        attach_media(&mut ipameta, &mut nftmeta, pinned.media.clone());
+        check_metadata(self.state, self.transcript, &ipameta, &nftmeta)?;
+
+        let (ip_metadata, nft_metadata) = metadata_json(&ipameta, &nftmeta)?;
+        let pinata = &self.state.adapters.pinata;
+        let ip_hash = pin_json(pinata, &ip_metadata).await?;
+        let nft_hash = pin_json(pinata, &nft_metadata).await?;
+
+        let gateway = &self.state.config.ipfs_gateway;
+        Ok(UploadedMetadata {
+            ip_metadata,
+            nft_metadata,
+            ip_metadata_url: format!("{}{}", gateway, ip_hash),
+            nft_metadata_url: format!("{}{}", gateway, nft_hash),
+            pins: vec![ip_hash, nft_hash],
+        })
+    }
+
+    fn compensate(
+        &self,
+        output: UploadedMetadata,
+    ) -> impl Future<Output = Result<(), MainProcessError>> + Send + 'static {
+        unpin_all(self.state.adapters.pinata.clone(), output.pins)
+    }
+}
+
+/// Output of [`RegisterOnchain`]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Registered {
+    #[serde(
+        serialize_with = "serialize_fixed_bytes",
+        deserialize_with = "deserialize_fixed_bytes"
+    )]
+    pub transaction_hash: FixedBytes<32>,
+    #[serde(
+        serialize_with = "serialize_address",
+        deserialize_with = "deserialize_address"
+    )]
+    pub ip_id: Address,
+}
+
+/// Registers the IP with the registry contract, the point after which nothing is undone
+pub struct RegisterOnchain<'a> {
+    pub state: &'a AppState,
+    pub content: &'a ContentSchema,
+}
+
+impl Stage for RegisterOnchain<'_> {
+    /// The title and the pinned metadata
+    type Input = (String, UploadedMetadata);
+    type Output = Registered;
+
+    const NAME: StageName = StageName::RegisterOnchain;
+    const IRREVERSIBLE: bool = true;
+
+    // a transaction cut short may still land, it is never retried
+    fn policy(&self) -> StagePolicy {
+        StagePolicy::ONCE
+    }
+
+    async fn run(
+        &self,
+        (title, uploaded): &(String, UploadedMetadata),
+    ) -> Result<Registered, MainProcessError> {
+        let regip = registrar::register_ip(
+            &self.state.adapters.registrar,
+            self.content.address,
+            title.clone(),
+            uploaded.ip_metadata_url.clone(),
+            uploaded.ip_metadata.clone(),
+            uploaded.nft_metadata_url.clone(),
+            uploaded.nft_metadata.clone(),
+            self.content.app_id.clone(),
+            self.content.submitter,
+        )
+        .await
+        .map_err(|e| MainProcessError::Registration(e.to_string()))?;
+
+        Ok(Registered {
+            transaction_hash: regip.hash,
+            ip_id: regip.ipid,
+        })
+    }
+}
+
+/// Output of [`PublishDa`]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Published {
+    /// EigenDA request ID of the blob holding the proof of task
+    pub request_id: String,
+}
+
+/// Publishes the proof of task to EigenDA for validators to fetch
+pub struct PublishDa<'a> {
+    pub state: &'a AppState,
+    pub transcript: &'a VerificationResult,
+}
+
+impl Stage for PublishDa<'_> {
+    type Input = ProofofTask;
+    type Output = Published;
+
+    const NAME: StageName = StageName::PublishDa;
+    // the proof of task carries the time the job was verified at, which a resumed job stamps anew
+    const REPLAYED: bool = true;
+
+    // dispersal is polled for up to five minutes before it fails
+    fn policy(&self) -> StagePolicy {
+        StagePolicy::retrying(3, Duration::from_secs(6 * 60))
+    }
+
+    async fn run(&self, proof: &ProofofTask) -> Result<Published, MainProcessError> {
+        // the whole proof goes to DA, whatever the prover left unredacted becomes public
+        for secret in self
+            .state
+            .config
+            .sensitive
+            .unredacted_secrets(self.transcript)
+        {
+            println!(
+                "Warning: publishing a proof with an unredacted secret, {}.",
+                secret
+            );
+        }
+
+        let proof = serde_json::to_string(proof)
+            .map_err(|e| eyre::eyre!("Failed to serialize the proof of task: {}", e))?;
+        let request_id = publish_blob(&self.state.config.eigenda_endpoint, format!("00{}", proof))
+            .await
+            .map_err(|e| eyre::eyre!("Failed to publish the proof to EigenDA: {}", e))?;
+        Ok(Published { request_id })
+    }
+}
+
+/// Submits the task to the Othentic aggregator
+pub struct SubmitTask<'a> {
+    pub state: &'a AppState,
+}
+
+impl Stage for SubmitTask<'_> {
+    type Input = Published;
+    type Output = ();
+
+    const NAME: StageName = StageName::SubmitTask;
+
+    // a task that reached the aggregator is validated again if sent twice, and one cut short
+    // may have reached it
+    fn policy(&self) -> StagePolicy {
+        StagePolicy::ONCE
+    }
+
+    async fn run(&self, published: &Published) -> Result<(), MainProcessError> {
+        send_task(
+            &self.state.adapters.othentic,
+            published.request_id.clone(),
+            0,
+        )
+        .await
+        .map_err(|e| eyre::eyre!("Failed to submit the task: {}", e))?;
+        Ok(())
+    }
+}
+
+// pins a file or JSON property from its source, URL properties aren't pinned
+async fn pin_media(
+    config: &PinataConfig,
+    property: &Property,
+    source: &str,
+) -> Result<Option<String>, MainProcessError> {
+    let ipfs_hash = match property.property_type {
+        PropertyType::File => {
+            pinata::upload_file_from_url(
+                config,
+                pinata::FileUploadParams {
+                    file_url: source.to_string(),
+                    file_name: property.key.clone(),
+                    file_type: property.mime.clone(),
+                },
+            )
+            .await
+            .map_err(|e| MainProcessError::BadFileUse(e.to_string()))?
+            .ipfs_hash
+        }
+        PropertyType::Json => pin_json(config, source).await?,
+        PropertyType::Url => return Ok(None),
+    };
+    Ok(Some(ipfs_hash))
+}
+
+// a document that isn't JSON won't become JSON on a retry, only Pinata failures are retried
+async fn pin_json(config: &PinataConfig, json: &str) -> Result<String, MainProcessError> {
+    let document = Value::from_str(json).map_err(|e| {
+        MainProcessError::BadContentSchema(format!("Failed to pin invalid JSON: {}", e))
+    })?;
+    Ok(pinata::upload_json(config, document)
+        .await
+        .map_err(|e| MainProcessError::BadFileUse(e.to_string()))?
+        .ipfs_hash)
+}
+
+// every pin is tried, the ones that failed are reported together
+async fn unpin_all(config: PinataConfig, pins: Vec<String>) -> Result<(), MainProcessError> {
+    let mut failed = Vec::new();
+    for pin in &pins {
+        if let Err(e) = pinata::unpin(&config, pin).await.map_err(|e| e.to_string()) {
+            failed.push(format!("{} ({})", pin, e));
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(MainProcessError::BadFileUse(format!(
+            "Failed to unpin {}",
+            failed.join(", ")
+        )))
+    }
+}
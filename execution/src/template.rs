@@ -0,0 +1,252 @@
+use std::fmt::Write;
+use verifier::VerificationResult;
+
+use crate::{
+    error::MainProcessError::{self, BadContentSchema},
+    transform::{parse_timestamp, Transforms},
+    utils::extract_string,
+};
+
+/// Renders `{{key}}` placeholders with values from the transcript.
+///
+/// A placeholder holds a content key followed by filters separated by ` | `, e.g.
+/// `{{received>data.text | truncate:40 | lowercase}}`. Supported filters are `trim`,
+/// `lowercase`, `uppercase`, `truncate:<chars>` and `date-format:<strftime>`.
+///
+/// # Arguments
+///
+/// * `template` - The text to render, plain text is returned unchanged.
+/// * `transcript` - The verified transcript placeholders are read from.
+/// * `transforms` - The schema's transforms, applied to values before the filters.
+pub fn render(
+    template: &str,
+    transcript: &VerificationResult,
+    transforms: &Transforms,
+) -> Result<String, MainProcessError> {
+    let mut rendered = String::with_capacity(template.len());
+    for piece in parse(template)? {
+        match piece {
+            Piece::Text(text) => rendered.push_str(text),
+            Piece::Placeholder { key, filters } => {
+                let mut value = extract_string(transcript, key, transforms)?;
+                for filter in filters {
+                    value = apply_filter(filter, value)?;
+                }
+                rendered.push_str(&value);
+            }
+        }
+    }
+    Ok(rendered)
+}
+
+/// Lists the content keys a template reads, without touching a transcript.
+///
+/// # Arguments
+///
+/// * `template` - The text holding placeholders.
+pub fn keys(template: &str) -> Result<Vec<&str>, MainProcessError> {
+    Ok(parse(template)?
+        .into_iter()
+        .filter_map(|piece| match piece {
+            Piece::Placeholder { key, .. } => Some(key),
+            Piece::Text(_) => None,
+        })
+        .collect())
+}
+
+// a template split into literal text and placeholders
+enum Piece<'a> {
+    Text(&'a str),
+    Placeholder { key: &'a str, filters: Vec<&'a str> },
+}
+
+fn parse(template: &str) -> Result<Vec<Piece<'_>>, MainProcessError> {
+    let mut pieces = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        pieces.push(Piece::Text(&rest[..start]));
+        let end = rest[start..].find("}}").ok_or_else(|| {
+            BadContentSchema(format!("Unclosed placeholder in template '{}'", template))
+        })?;
+
+        let placeholder = &rest[start + 2..start + end];
+        let mut parts = placeholder.split(" | ");
+        let key = parts.next().unwrap_or_default().trim();
+        if key.is_empty() {
+            return Err(BadContentSchema(format!(
+                "Empty placeholder in template '{}'",
+                template
+            )));
+        }
+        pieces.push(Piece::Placeholder {
+            key,
+            filters: parts.map(str::trim).collect(),
+        });
+
+        rest = &rest[start + end + 2..];
+    }
+
+    pieces.push(Piece::Text(rest));
+    Ok(pieces)
+}
+
+fn apply_filter(filter: &str, value: String) -> Result<String, MainProcessError> {
+    let (name, argument) = match filter.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument)),
+        None => (filter, None),
+    };
+
+    match (name, argument) {
+        ("trim", None) => Ok(value.trim().to_string()),
+        ("lowercase", None) => Ok(value.to_lowercase()),
+        ("uppercase", None) => Ok(value.to_uppercase()),
+        ("truncate", Some(length)) => {
+            let length: usize = length.trim().parse().map_err(|_| {
+                BadContentSchema(format!(
+                    "Filter 'truncate' needs a length, got '{}'",
+                    length
+                ))
+            })?;
+            Ok(value.chars().take(length).collect())
+        }
+        ("date-format", Some(format)) => {
+            let time = parse_timestamp(&value).ok_or_else(|| {
+                BadContentSchema(format!(
+                    "Filter 'date-format' can't read '{}' as a date",
+                    value
+                ))
+            })?;
+            // an invalid format string surfaces as a formatting error rather than a panic
+            let mut formatted = String::new();
+            write!(formatted, "{}", time.format(format)).map_err(|_| {
+                BadContentSchema(format!(
+                    "Filter 'date-format' has an invalid format '{}'",
+                    format
+                ))
+            })?;
+            Ok(formatted)
+        }
+        _ => Err(BadContentSchema(format!(
+            "Unknown template filter '{}'",
+            filter
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use tlsn_core::connection::ServerName;
+    use verifier::AuthenticatedRanges;
+
+    use super::*;
+
+    fn transcript() -> VerificationResult {
+        let received = r#"HTTP/1.1 200 OK
+Content-Type: application/json
+
+{"data":{"text":"Shipping The Forge today, come build with us","created_at":"Wed Oct 10 20:19:24 +0000 2018","user":"Forge"}}"#;
+        let sent = "GET /2/users/by/username/forge HTTP/1.1\r\nHost: api.x.com\r\n\r\n";
+        VerificationResult {
+            server_name: ServerName::new("api.x.com".to_string()),
+            time: Utc.with_ymd_and_hms(2024, 9, 19, 12, 23, 10).unwrap(),
+            notary: "test".to_string(),
+            sent_data: sent.to_string(),
+            received_data: received.to_string(),
+            sent_authed: AuthenticatedRanges::full(sent.len()),
+            received_authed: AuthenticatedRanges::full(received.len()),
+            sent_raw: sent.as_bytes().to_vec(),
+            received_raw: received.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_render_placeholders_and_filters() {
+        let transcript = transcript();
+
+        assert_eq!(
+            render("Static title", &transcript, &Transforms::new()).unwrap(),
+            "Static title"
+        );
+        assert_eq!(
+            render("Post by {{sent@path[4]}}", &transcript, &Transforms::new()).unwrap(),
+            "Post by forge"
+        );
+        assert_eq!(
+            render("{{received>data.user | uppercase}}: {{received>data.text | truncate:14 | lowercase}}", &transcript, &Transforms::new())
+            .unwrap(),
+            "FORGE: shipping the f"
+        );
+        assert_eq!(
+            render(
+                "Posted {{received>data.created_at | date-format:%Y-%m-%d %H:%M}}",
+                &transcript,
+                &Transforms::new()
+            )
+            .unwrap(),
+            "Posted 2018-10-10 20:19"
+        );
+    }
+
+    #[test]
+    fn test_render_applies_transforms() {
+        let transforms: Transforms = serde_json::from_str(
+            r#"{"received>data.created_at": ["parse-timestamp"], "received>data.user": ["lowercase"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            render(
+                "{{received>data.user}} at {{received>data.created_at}}",
+                &transcript(),
+                &transforms
+            )
+            .unwrap(),
+            "forge at 2018-10-10T20:19:24Z"
+        );
+    }
+
+    #[test]
+    fn test_template_keys() {
+        assert_eq!(
+            keys("{{received>data.user | uppercase}}: {{sent@path[4]}}").unwrap(),
+            vec!["received>data.user", "sent@path[4]"]
+        );
+        assert!(keys("Static title").unwrap().is_empty());
+        assert!(keys("{{received>data.text").is_err());
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let transcript = transcript();
+
+        assert!(render("{{received>data.text", &transcript, &Transforms::new()).is_err());
+        assert!(render("{{}}", &transcript, &Transforms::new()).is_err());
+        assert!(render("{{received>data.missing}}", &transcript, &Transforms::new()).is_err());
+        assert!(render(
+            "{{received>data.text | shout}}",
+            &transcript,
+            &Transforms::new()
+        )
+        .is_err());
+        assert!(render(
+            "{{received>data.text | truncate:many}}",
+            &transcript,
+            &Transforms::new()
+        )
+        .is_err());
+        assert!(render(
+            "{{received>data.text | date-format:%Y}}",
+            &transcript,
+            &Transforms::new()
+        )
+        .is_err());
+        assert!(render(
+            "{{received>data.created_at | date-format:%Q}}",
+            &transcript,
+            &Transforms::new()
+        )
+        .is_err());
+    }
+}
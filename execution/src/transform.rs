@@ -0,0 +1,244 @@
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::error::MainProcessError::{self, BadContentSchema};
+
+/// Transforms to apply to each content key, keyed by the content key
+pub type Transforms = BTreeMap<String, Vec<Transform>>;
+
+const PADDING_INDIFFERENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const BASE64: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, PADDING_INDIFFERENT);
+const BASE64_URL_SAFE: GeneralPurpose =
+    GeneralPurpose::new(&alphabet::URL_SAFE, PADDING_INDIFFERENT);
+
+/// A step applied to an extracted value before it is used in metadata
+///
+/// Declared in a schema as `"trim"` or, for steps taking arguments,
+/// `{"substring": {"start": 0, "length": 10}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Transform {
+    Trim,
+    Lowercase,
+    /// Parses the value as a JSON number, ignoring `,` and `_` digit separators
+    ParseNumber,
+    /// Parses a timestamp and writes it as RFC 3339 in UTC
+    ParseTimestamp,
+    Base64Decode,
+    UrlDecode,
+    /// Keeps `length` characters starting at character `start`, or the rest when unset
+    Substring {
+        start: usize,
+        #[serde(default)]
+        length: Option<usize>,
+    },
+}
+
+impl Transform {
+    fn name(&self) -> &'static str {
+        match self {
+            Transform::Trim => "trim",
+            Transform::Lowercase => "lowercase",
+            Transform::ParseNumber => "parse-number",
+            Transform::ParseTimestamp => "parse-timestamp",
+            Transform::Base64Decode => "base64-decode",
+            Transform::UrlDecode => "url-decode",
+            Transform::Substring { .. } => "substring",
+        }
+    }
+
+    /// Applies this step to a value.
+    pub fn apply(&self, value: Value) -> Result<Value, MainProcessError> {
+        let text = match &value {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        let failed =
+            |reason: &str| BadContentSchema(format!("Transform '{}' {}", self.name(), reason));
+
+        match self {
+            Transform::Trim => Ok(Value::String(text.trim().to_string())),
+            Transform::Lowercase => Ok(Value::String(text.to_lowercase())),
+            Transform::ParseNumber => {
+                if value.is_number() {
+                    return Ok(value);
+                }
+                let digits: String = text
+                    .trim()
+                    .chars()
+                    .filter(|c| *c != ',' && *c != '_')
+                    .collect();
+                serde_json::from_str::<serde_json::Number>(&digits)
+                    .map(Value::Number)
+                    .map_err(|_| failed(&format!("can't read '{}' as a number", text)))
+            }
+            Transform::ParseTimestamp => parse_timestamp(&text)
+                .map(|time| Value::String(time.to_rfc3339_opts(SecondsFormat::Secs, true)))
+                .ok_or_else(|| failed(&format!("can't read '{}' as a timestamp", text))),
+            Transform::Base64Decode => {
+                let encoded = text.trim();
+                let bytes = BASE64
+                    .decode(encoded)
+                    .or_else(|_| BASE64_URL_SAFE.decode(encoded))
+                    .map_err(|e| failed(&format!("got invalid base64: {}", e)))?;
+                String::from_utf8(bytes)
+                    .map(Value::String)
+                    .map_err(|_| failed("decoded to bytes that aren't UTF-8"))
+            }
+            Transform::UrlDecode => percent_encoding::percent_decode_str(&text)
+                .decode_utf8()
+                .map(|decoded| Value::String(decoded.to_string()))
+                .map_err(|_| failed("decoded to bytes that aren't UTF-8")),
+            Transform::Substring { start, length } => {
+                let chars = text.chars().skip(*start);
+                Ok(Value::String(match length {
+                    Some(length) => chars.take(*length).collect(),
+                    None => chars.collect(),
+                }))
+            }
+        }
+    }
+}
+
+/// Applies transforms in order.
+///
+/// # Arguments
+///
+/// * `key` - The content key the value was extracted with, used in errors.
+/// * `value` - The extracted value.
+/// * `transforms` - The steps to apply.
+pub fn apply_all(
+    key: &str,
+    value: Value,
+    transforms: &[Transform],
+) -> Result<Value, MainProcessError> {
+    transforms.iter().try_fold(value, |value, transform| {
+        transform.apply(value).map_err(|e| match e {
+            BadContentSchema(reason) => BadContentSchema(format!("{} for '{}'", reason, key)),
+            other => other,
+        })
+    })
+}
+
+/// Reads a timestamp as RFC 3339, RFC 2822, the `Wed Oct 10 20:19:24 +0000 2018` format X uses,
+/// or unix seconds.
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_rfc2822(value))
+        .or_else(|_| DateTime::parse_from_str(value, "%a %b %d %H:%M:%S %z %Y"))
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            value
+                .parse::<i64>()
+                .ok()
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+
+    fn apply(transforms: &str, value: Value) -> Result<Value, MainProcessError> {
+        let transforms: Vec<Transform> = serde_json::from_str(transforms).unwrap();
+        apply_all("received>value", value, &transforms)
+    }
+
+    #[test]
+    fn test_transforms_in_order() {
+        assert_eq!(
+            apply(r#"["trim", "lowercase"]"#, json!("  The Forge ")).unwrap(),
+            json!("the forge")
+        );
+        assert_eq!(
+            apply(r#"["trim", "parse-number"]"#, json!(" 1,024 ")).unwrap(),
+            json!(1024)
+        );
+        assert_eq!(
+            apply(r#"["parse-number"]"#, json!("0.5")).unwrap(),
+            json!(0.5)
+        );
+        assert_eq!(
+            apply(
+                r#"["parse-timestamp"]"#,
+                json!("Wed Oct 10 20:19:24 +0000 2018")
+            )
+            .unwrap(),
+            json!("2018-10-10T20:19:24Z")
+        );
+        assert_eq!(
+            apply(
+                r#"["base64-decode", "url-decode"]"#,
+                json!("aGVsbG8lMjB3b3JsZA")
+            )
+            .unwrap(),
+            json!("hello world")
+        );
+        assert_eq!(
+            apply(
+                r#"[{"substring": {"start": 4}}, {"substring": {"start": 0, "length": 5}}]"#,
+                json!("the forge app")
+            )
+            .unwrap(),
+            json!("forge")
+        );
+
+        // the order matters
+        let strip_currency = r#"{"substring": {"start": 1}}"#;
+        assert_eq!(
+            apply(
+                &format!(r#"[{}, "parse-number"]"#, strip_currency),
+                json!("$12")
+            )
+            .unwrap(),
+            json!(12)
+        );
+        assert!(apply(
+            &format!(r#"["parse-number", {}]"#, strip_currency),
+            json!("$12")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_transform_errors_name_the_key() {
+        let error = apply(r#"["parse-number"]"#, json!("many")).unwrap_err();
+        assert!(error.to_string().contains("parse-number"));
+        assert!(error.to_string().contains("received>value"));
+
+        assert!(apply(r#"["parse-timestamp"]"#, json!("yesterday")).is_err());
+        assert!(apply(r#"["base64-decode"]"#, json!("not base64!")).is_err());
+        assert!(serde_json::from_str::<Vec<Transform>>(r#"["shout"]"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = Utc.with_ymd_and_hms(2018, 10, 10, 20, 19, 24).unwrap();
+
+        assert_eq!(parse_timestamp("2018-10-10T20:19:24Z"), Some(expected));
+        assert_eq!(
+            parse_timestamp("Wed, 10 Oct 2018 20:19:24 +0000"),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_timestamp("Wed Oct 10 20:19:24 +0000 2018"),
+            Some(expected)
+        );
+        assert_eq!(parse_timestamp("1539202764"), Some(expected));
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+}
@@ -8,15 +8,10 @@ pub struct ZKProofVerificationResult {
     pub public_data: Value,
 }
 
-// Function to generate the proof
-pub async fn generate_zk_proof(input: &str) -> Result<Value, String> {
-    // Obtain the user's API key from the .env file.
-    dotenv::dotenv().map_err(|e| format!("Failed to read .env file: {}", e))?;
-    let api_key: String = std::env::var("SINDRI_API_KEY")
-        .map_err(|e| format!("Failed to get SINDRI_API_KEY: {}", e))?;
-
+// Function to generate the proof with the given Sindri API key
+pub async fn generate_zk_proof(api_key: &str, input: &str) -> Result<Value, String> {
     // Create a headers map with the API key.
-    let header = headers_json(&api_key);
+    let header = headers_json(api_key);
 
     // Generate proof using the input
     Ok(prove_guest_code(input, header).await)
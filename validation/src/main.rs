@@ -1,3 +1,4 @@
+mod presentations;
 mod server;
 mod service;
 
@@ -5,5 +6,7 @@ use server::run_server;
 
 #[tokio::main]
 async fn main() {
-    let _ = run_server().await;
+    if let Err(e) = run_server().await {
+        eprintln!("Validation server stopped: {}", e.message);
+    }
 }
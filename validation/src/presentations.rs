@@ -0,0 +1,88 @@
+use alloy_primitives::{Address, FixedBytes};
+use std::path::Path;
+
+/// The IP each validated presentation registered, so a proof can't back two IPs
+pub struct PresentationIndex {
+    db: sled::Db,
+}
+
+impl PresentationIndex {
+    /// Opens the index in a directory, creating it if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory the index lives in.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, sled::Error> {
+        Ok(PresentationIndex {
+            db: sled::open(path)?,
+        })
+    }
+
+    /// Opens an index that is deleted once dropped.
+    #[cfg(test)]
+    fn temporary() -> Result<Self, sled::Error> {
+        Ok(PresentationIndex {
+            db: sled::Config::new().temporary(true).open()?,
+        })
+    }
+
+    /// Records that a presentation registered an IP, returning the other IP it already
+    /// registered if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `presentation` - Hash of the presentation alone, so every schema shares one entry.
+    /// * `ip_id` - The IP the task registered.
+    pub fn claim(
+        &self,
+        presentation: FixedBytes<32>,
+        ip_id: Address,
+    ) -> Result<Option<Address>, sled::Error> {
+        let claimed =
+            self.db
+                .compare_and_swap(presentation, None::<&[u8]>, Some(ip_id.as_slice()))?;
+        self.db.flush()?;
+
+        // validating the same task again is fine
+        Ok(match claimed {
+            Ok(()) => None,
+            Err(e) => e
+                .current
+                .map(|current| Address::from_slice(&current))
+                .filter(|current| *current != ip_id),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::keccak256;
+
+    #[test]
+    fn test_a_presentation_backs_a_single_ip() {
+        let index = PresentationIndex::temporary().unwrap();
+        let first = Address::repeat_byte(1);
+        let second = Address::repeat_byte(2);
+
+        assert_eq!(index.claim(keccak256("a"), first).unwrap(), None);
+        // the same task is validated again, another IP is refused
+        assert_eq!(index.claim(keccak256("a"), first).unwrap(), None);
+        assert_eq!(index.claim(keccak256("a"), second).unwrap(), Some(first));
+    }
+
+    #[test]
+    fn test_presentations_are_claimed_separately() {
+        let index = PresentationIndex::temporary().unwrap();
+        let ip_id = Address::repeat_byte(1);
+
+        assert_eq!(index.claim(keccak256("a"), ip_id).unwrap(), None);
+        assert_eq!(index.claim(keccak256("b"), ip_id).unwrap(), None);
+        assert_eq!(
+            index
+                .claim(keccak256("b"), Address::repeat_byte(2))
+                .unwrap(),
+            Some(ip_id)
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::presentations::PresentationIndex;
 use crate::service::{verify_ip_from_proof, ErrorResponse};
 
 use axum::{
@@ -6,7 +7,20 @@ use axum::{
     routing::{get, post},
     Router,
 };
+use execution::config::{ForgeConfig, Service};
+use execution::schema_registry::SchemaRegistry;
 use serde_json::json;
+use std::sync::Arc;
+
+/// State shared by every request handler
+pub struct AppState {
+    /// Settings shared with the execution service, trusted notaries and proof policy included
+    pub config: ForgeConfig,
+    /// Content schemas tasks are checked against, the same registry the execution nodes serve
+    pub schemas: SchemaRegistry,
+    /// The IP each validated presentation registered
+    pub presentations: PresentationIndex,
+}
 
 async fn health_check() -> impl IntoResponse {
     (StatusCode::OK, "Ok")
@@ -23,12 +37,29 @@ async fn root() -> impl IntoResponse {
 }
 
 pub async fn run_server() -> Result<(), ErrorResponse> {
+    let config = ForgeConfig::load(Service::Validation)
+        .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?;
+    let schemas = match &config.schema_dir {
+        Some(dir) => SchemaRegistry::from_dir(dir),
+        None => Ok(SchemaRegistry::default()),
+    }
+    .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?;
+    let presentations = PresentationIndex::open(&config.presentation_index)
+        .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?;
+    let listen_address = config.listen_address;
+    let state = Arc::new(AppState {
+        config,
+        schemas,
+        presentations,
+    });
+
     let router = Router::new()
         .route("/", get(root))
         .route("/health", get(health_check))
-        .route("/task/validate", post(verify_ip_from_proof));
+        .route("/task/validate", post(verify_ip_from_proof))
+        .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:2078")
+    let listener = tokio::net::TcpListener::bind(listen_address)
         .await
         .map_err(|e| {
             ErrorResponse::new(json!({}), &format!("Failed to bind TCP listener: {}", e))
@@ -1,12 +1,22 @@
+use crate::server::AppState;
+
 use alloy_primitives::{Address, FixedBytes};
-use axum::{http::StatusCode, response::IntoResponse, Json};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
 use eigenda::retrieve_blob;
-use execution::utils::{get_content_data, ContentSchema, Input};
+use execution::assertion::check_assertions;
+use execution::jobs::canonical_presentation_hash;
+use execution::utils::{
+    get_schema_attributes, get_schema_creators, ContentSchema, Input, MediaSlots,
+};
 use registrar::get_transaction_data;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::error::Error;
-use verifier::{verify_proof_from_json, VerificationResult};
+use std::sync::Arc;
+use verifier::{
+    verify_proof_from_bytes, NotaryTrustStore, VerificationPolicy, VerificationResult,
+    VerifierError,
+};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -64,7 +74,11 @@ pub struct ProofofTask {
     pub transcript_proof: String,
     pub transaction_hash: FixedBytes<32>,
     pub ip_id: Address,
-    pub content_schema: ContentSchema,
+    pub schema_id: String,
+    pub address: Address,
+    pub app_id: String,
+    pub submitter: Address,
+    pub verified_at: DateTime<Utc>,
 }
 
 #[derive(Serialize)]
@@ -92,6 +106,15 @@ impl IntoResponse for ErrorResponse {
     }
 }
 
+impl From<VerifierError> for ErrorResponse {
+    fn from(e: VerifierError) -> Self {
+        ErrorResponse::new(
+            json!({ "reason": e }),
+            &format!("Transcript proof is invalid: {}", e),
+        )
+    }
+}
+
 impl CustomResponse {
     pub fn new(data: serde_json::Value, message: &str) -> Self {
         CustomResponse {
@@ -112,21 +135,60 @@ impl ErrorResponse {
 }
 
 pub async fn verify_ip_from_proof(
+    State(state): State<Arc<AppState>>,
     Json(body): Json<ValidateRequest>,
 ) -> Result<impl IntoResponse, ErrorResponse> {
     // Parse and verify proof
-    let proof_of_task = retrieve_blob(body.proof_of_task).await.unwrap();
-    let proof = serde_json::from_str::<ProofofTask>(&proof_of_task).unwrap();
-    let verification_result = verify(Input {
-        transcript_proof: proof.transcript_proof.clone(),
-    })
-    .map_err(|_| ErrorResponse::new(json!({}), "Transcript proof is invalid"));
+    let proof_of_task = retrieve_blob(&state.config.eigenda_endpoint, body.proof_of_task)
+        .await
+        .map_err(|e| {
+            ErrorResponse::new(
+                json!({}),
+                &format!("Failed to retrieve the proof of task: {}", e),
+            )
+        })?;
+    let proof = serde_json::from_str::<ProofofTask>(&proof_of_task).map_err(|e| {
+        ErrorResponse::new(json!({}), &format!("The proof of task is malformed: {}", e))
+    })?;
+
+    // Judge freshness when the performer verified the proof, the task reaches us later. The
+    // performer picks that time, the policy only trusts it within a bounded lag of our clock
+    let policy = state.config.policy.for_task(proof.verified_at)?;
+
+    // the task only names the schema, validators hold their own copy of the registry
+    let schema = state
+        .schemas
+        .get(&proof.schema_id)
+        .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?;
+    if schema.reference() != proof.schema_id {
+        return Err(ErrorResponse::new(
+            json!({}),
+            "The task must name an exact schema version",
+        ));
+    }
+    let content = schema.bind(proof.address, proof.app_id.clone(), proof.submitter);
+
+    let verification_result = verify(
+        Input {
+            transcript_proof: proof.transcript_proof.clone(),
+        },
+        &state.config.trust_store,
+        &policy,
+    )?;
+
+    // the performer must not have registered a transcript the schema's assertions reject
+    check_assertions(
+        &verification_result,
+        &content.assertions,
+        &content.metadata.transforms,
+    )
+    .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?;
 
     // Verify transaction data
-    let ip_data = get_transaction_data(proof.transaction_hash)
+    let ip_data = get_transaction_data(&state.config.story_rpc_url, proof.transaction_hash)
         .await
         .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?;
-    if ip_data.owner != proof.content_schema.address {
+    if ip_data.owner != content.address {
         return Err(ErrorResponse::new(
             json!({}),
             "The owner of the IP doesn't follow what was given in the content schema",
@@ -141,11 +203,25 @@ pub async fn verify_ip_from_proof(
     }
 
     // Fetch metadata
-    let ipameta = fetch_ipameta(&ip_data.ipMetadataURI).await?;
-    let nftmeta = fetch_nftmeta(&ip_data.nftMetadataURI).await?;
+    let gateway = &state.config.ipfs_gateway;
+    let ipameta = fetch_ipameta(gateway, &ip_data.ipMetadataURI).await?;
+    let nftmeta = fetch_nftmeta(gateway, &ip_data.nftMetadataURI).await?;
 
     // Validate metadata consistency
-    validate_metadata_consistency(&proof, &ipameta, &nftmeta, &verification_result?)?;
+    validate_metadata_consistency(&content, &ipameta, &nftmeta, &verification_result)?;
+
+    // a proof backs a single IP, however many times and under whichever schema it was submitted
+    let presentation = canonical_presentation_hash(&proof.transcript_proof)?;
+    let claimed = state
+        .presentations
+        .claim(presentation, proof.ip_id)
+        .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?;
+    if let Some(other) = claimed {
+        return Err(ErrorResponse::new(
+            json!({ "presentationHash": presentation, "ipId": other }),
+            "The transcript proof was already used to register another IP",
+        ));
+    }
 
     // TODO: handle other cases. rn these are satisfactory and can verify the execution was handled correctly
 
@@ -155,8 +231,8 @@ pub async fn verify_ip_from_proof(
     ))
 }
 
-async fn fetch_ipameta(uri: &str) -> Result<IPAMeta, ErrorResponse> {
-    reqwest::get(&format!("https://ipfs.io/ipfs/{}", uri))
+async fn fetch_ipameta(gateway: &str, uri: &str) -> Result<IPAMeta, ErrorResponse> {
+    reqwest::get(&format!("{}{}", gateway, uri))
         .await
         .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?
         .json()
@@ -164,8 +240,8 @@ async fn fetch_ipameta(uri: &str) -> Result<IPAMeta, ErrorResponse> {
         .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))
 }
 
-async fn fetch_nftmeta(uri: &str) -> Result<NFTMeta, ErrorResponse> {
-    reqwest::get(&format!("https://ipfs.io/ipfs/{}", uri))
+async fn fetch_nftmeta(gateway: &str, uri: &str) -> Result<NFTMeta, ErrorResponse> {
+    reqwest::get(&format!("{}{}", gateway, uri))
         .await
         .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?
         .json()
@@ -174,18 +250,34 @@ async fn fetch_nftmeta(uri: &str) -> Result<NFTMeta, ErrorResponse> {
 }
 
 fn validate_metadata_consistency(
-    proof: &ProofofTask,
+    content: &ContentSchema,
     ipameta: &IPAMeta,
     nftmeta: &NFTMeta,
     verification_result: &VerificationResult,
 ) -> Result<(), ErrorResponse> {
-    if proof.content_schema.name != ipameta.title {
+    // names and descriptions are templates, render them again from the verified transcript
+    let render = |template: &str| {
+        execution::template::render(template, verification_result, &content.metadata.transforms)
+            .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))
+    };
+    if render(&content.name)? != ipameta.title {
         return Err(ErrorResponse::new(
             json!({}),
             "The name of the content schema doesn't match the IP metadata",
         ));
     }
 
+    let description = match content.metadata.properties.first() {
+        Some(property) => render(&property.description)?,
+        None => String::new(),
+    };
+    if description != ipameta.description || description != nftmeta.description {
+        return Err(ErrorResponse::new(
+            json!({}),
+            "The description of the content schema doesn't match the IP and NFT metadata",
+        ));
+    }
+
     if ipameta.title != nftmeta.name {
         return Err(ErrorResponse::new(
             json!({}),
@@ -193,40 +285,96 @@ fn validate_metadata_consistency(
         ));
     }
 
-    if ipameta.attributes[0].value
-        != Value::String(verification_result.server_name.as_str().to_string())
+    // every declared property must have produced its media entry, in order
+    let properties = &content.metadata.properties;
+    if ipameta.media.len() != properties.len()
+        || ipameta
+            .media
+            .iter()
+            .zip(properties)
+            .any(|(media, property)| media.mimetype != property.mime)
     {
         return Err(ErrorResponse::new(
             json!({}),
-            "The server name in the IP metadata doesn't match the server name in the transcript proof",
+            "The media in the IP metadata doesn't match the properties of the content schema",
         ));
     }
 
-    if ipameta.creators[0].name
-        != get_content_data(verification_result, &proof.content_schema.metadata.owner).unwrap()
+    let slots = MediaSlots::from_mimes(ipameta.media.iter().map(|media| media.mimetype.as_str()));
+    let slot_url = |slot: Option<usize>| slot.map(|index| ipameta.media[index].url.clone());
+    if nftmeta.image_url != slot_url(slots.image)
+        || nftmeta.audio_url != slot_url(slots.audio)
+        || nftmeta.animation_url != slot_url(slots.animation)
     {
         return Err(ErrorResponse::new(
             json!({}),
-            "The server name in the IP metadata doesn't match the server name in the transcript proof",
+            "The NFT media URLs don't match the first image, audio and video of the IP metadata",
         ));
     }
 
-    if ipameta.creators[0].address != proof.content_schema.address {
+    let source = Value::String(verification_result.server_name.as_str().to_string());
+    if ipameta.attributes.first().map(|attribute| &attribute.value) != Some(&source) {
         return Err(ErrorResponse::new(
             json!({}),
             "The server name in the IP metadata doesn't match the server name in the transcript proof",
         ));
     }
 
+    // re-extract every declared attribute, they follow `Source` in declaration order
+    let expected = get_schema_attributes(verification_result, content)
+        .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?;
+    let published = ipameta
+        .attributes
+        .iter()
+        .skip(1)
+        .map(|attribute| (attribute.key.clone(), attribute.value.clone()))
+        .collect::<Vec<_>>();
+    if published != expected {
+        return Err(ErrorResponse::new(
+            json!({ "expected": expected, "published": published }),
+            "The attributes in the IP metadata don't match the ones declared in the content schema",
+        ));
+    }
+
+    // every creator must be published with the name, address and share the schema gives it
+    let expected = get_schema_creators(verification_result, content)
+        .map_err(|e| ErrorResponse::new(json!({}), &e.to_string()))?;
+    let matches = ipameta.creators.len() == expected.len()
+        && ipameta
+            .creators
+            .iter()
+            .zip(&expected)
+            .all(|(published, expected)| {
+                published.name == expected.name
+                    && published.address == expected.address
+                    && published.contribution_percent == expected.contribution_percent
+            });
+    if !matches {
+        return Err(ErrorResponse::new(
+            json!({ "expected": expected, "published": ipameta.creators }),
+            "The creators in the IP metadata don't match the ones declared in the content schema",
+        ));
+    }
+
     Ok(())
 }
 
-fn verify(pre_image: Input) -> Result<VerificationResult, Box<dyn Error>> {
-    let result = verify_proof_from_json(pre_image.transcript_proof.as_str())?;
+fn verify(
+    pre_image: Input,
+    trust_store: &NotaryTrustStore,
+    policy: &VerificationPolicy,
+) -> Result<VerificationResult, VerifierError> {
+    let result =
+        verify_proof_from_bytes(pre_image.transcript_proof.as_bytes(), trust_store, policy)?;
     Ok(VerificationResult {
         server_name: result.server_name,
         time: result.time,
+        notary: result.notary,
         sent_data: result.sent_data,
         received_data: result.received_data,
+        sent_authed: result.sent_authed,
+        received_authed: result.received_authed,
+        sent_raw: result.sent_raw,
+        received_raw: result.received_raw,
     })
 }